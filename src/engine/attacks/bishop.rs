@@ -1,13 +1,63 @@
 use super::generated::bishop::{BISHOP_BASE, BISHOP_LOOKUP, BISHOP_MASKS};
-use super::utility::pext;
+use super::generated::magic::{BISHOP_MAGICS, BISHOP_MAGIC_LOOKUP, BISHOP_SHIFTS};
+use super::utility::{pext, slider_backend, SliderBackend};
 
 #[inline(always)]
 pub fn get_bishop_mv(sq: usize, own: u64, enemy: u64) -> u64 {
     let occupancy = own | enemy;
+    let attacks = match slider_backend() {
+        SliderBackend::Pext => get_bishop_pext_att(sq, occupancy),
+        SliderBackend::Magic => get_bishop_magic_att(sq, occupancy),
+    };
+
+    attacks & !own
+}
+
+#[inline(always)]
+pub fn get_bishop_pext_att(sq: usize, occupancy: u64) -> u64 {
     let key = pext(occupancy, BISHOP_MASKS[sq]) as usize;
 
-    BISHOP_LOOKUP[BISHOP_BASE[sq] * 32 + key] & !own
+    BISHOP_LOOKUP[BISHOP_BASE[sq] * 32 + key]
+}
+
+#[inline(always)]
+pub fn get_bishop_magic_att(sq: usize, occupancy: u64) -> u64 {
+    let key = ((occupancy & BISHOP_MASKS[sq]).wrapping_mul(BISHOP_MAGICS[sq]) >> BISHOP_SHIFTS[sq])
+        as usize;
+
+    BISHOP_MAGIC_LOOKUP[BISHOP_BASE[sq] * 32 + key]
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use super::*;
+    use crate::engine::attacks::rays::slider_ray_att;
+    use crate::engine::shared::structures::directions::Dir;
+
+    const BISHOP_DIRS: [Dir; 4] = [Dir::NORTHEAST, Dir::SOUTHWEST, Dir::NORTHWEST, Dir::SOUTHEAST];
+
+    fn check_backend(att: fn(usize, u64) -> u64) {
+        for (sq, &mask) in BISHOP_MASKS.iter().enumerate() {
+            let mut occupancy = 0u64;
+            loop {
+                assert_eq!(att(sq, occupancy), slider_ray_att(sq, &BISHOP_DIRS, 0, occupancy));
+                occupancy = occupancy.wrapping_sub(mask) & mask;
+                if occupancy == 0 {
+                    break;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_bishop_magic_att() {
+        check_backend(get_bishop_magic_att);
+    }
+
+    #[test]
+    fn test_bishop_pext_att() {
+        if slider_backend() == SliderBackend::Pext {
+            check_backend(get_bishop_pext_att);
+        }
+    }
+}
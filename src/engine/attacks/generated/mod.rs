@@ -1,6 +1,7 @@
 pub mod bishop;
 pub mod king;
 pub mod knight;
+pub mod magic;
 pub mod pawn;
 pub mod rays;
 pub mod rook;
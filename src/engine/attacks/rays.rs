@@ -1,3 +1,4 @@
+use super::generated::rays::RAYS_LOOKUP;
 use crate::engine::shared::helper_func::bitboard::BitboardTrait;
 use crate::engine::shared::structures::directions::*;
 
@@ -5,9 +6,9 @@ pub fn first_hit(dir: Dir, bitboard: u64) -> Option<usize> {
     if bitboard == 0 {
         None
     } else if dir.is_forward() {
-        Some(bitboard.get_msb())
-    } else {
         Some(bitboard.get_lsb())
+    } else {
+        Some(bitboard.get_msb())
     }
 }
 
@@ -25,6 +26,14 @@ pub fn blocked_ray_att(dir: Dir, ray_family: &[u64; 64], ray: u64, own: u64, ene
     }
 }
 
+/// Slow ray-walking slider attacks, used as the reference for the lookup backends.
+pub fn slider_ray_att(sq: usize, dirs: &[Dir], own: u64, enemy: u64) -> u64 {
+    dirs.iter().fold(0, |att, dir| {
+        let ray_family = &RAYS_LOOKUP[dir.idx()];
+        att | blocked_ray_att(*dir, ray_family, ray_family[sq], own, enemy)
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use crate::engine::attacks::generated::rays::RAYS_LOOKUP;
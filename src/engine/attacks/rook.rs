@@ -1,13 +1,72 @@
+use super::generated::magic::{ROOK_MAGICS, ROOK_MAGIC_LOOKUP, ROOK_SHIFTS};
 use super::generated::rook::{ROOK_BASE, ROOK_LOOKUP, ROOK_MASKS};
-use super::utility::pext;
+use super::utility::{pext, slider_backend, SliderBackend};
 
 #[inline(always)]
 pub fn get_rook_mv(sq: usize, own: u64, enemy: u64) -> u64 {
     let occupancy = own | enemy;
+    let attacks = match slider_backend() {
+        SliderBackend::Pext => get_rook_pext_att(sq, occupancy),
+        SliderBackend::Magic => get_rook_magic_att(sq, occupancy),
+    };
+
+    attacks & !own
+}
+
+#[inline(always)]
+pub fn get_rook_pext_att(sq: usize, occupancy: u64) -> u64 {
     let key = pext(occupancy, ROOK_MASKS[sq]) as usize;
 
-    ROOK_LOOKUP[ROOK_BASE[sq] * 1024 + key] & !own
+    ROOK_LOOKUP[ROOK_BASE[sq] * 1024 + key]
+}
+
+#[inline(always)]
+pub fn get_rook_magic_att(sq: usize, occupancy: u64) -> u64 {
+    let key =
+        ((occupancy & ROOK_MASKS[sq]).wrapping_mul(ROOK_MAGICS[sq]) >> ROOK_SHIFTS[sq]) as usize;
+
+    ROOK_MAGIC_LOOKUP[ROOK_BASE[sq] * 1024 + key]
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use super::*;
+    use crate::engine::attacks::rays::slider_ray_att;
+    use crate::engine::shared::structures::directions::Dir;
+
+    const ROOK_DIRS: [Dir; 4] = [Dir::NORTH, Dir::SOUTH, Dir::EAST, Dir::WEST];
+
+    fn check_backend(att: fn(usize, u64) -> u64) {
+        for (sq, &mask) in ROOK_MASKS.iter().enumerate() {
+            let mut occupancy = 0u64;
+            loop {
+                assert_eq!(att(sq, occupancy), slider_ray_att(sq, &ROOK_DIRS, 0, occupancy));
+                occupancy = occupancy.wrapping_sub(mask) & mask;
+                if occupancy == 0 {
+                    break;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_rook_magic_att() {
+        check_backend(get_rook_magic_att);
+    }
+
+    #[test]
+    fn test_rook_pext_att() {
+        if slider_backend() == SliderBackend::Pext {
+            check_backend(get_rook_pext_att);
+        }
+    }
+
+    #[test]
+    fn test_rook_mv_excludes_own() {
+        let own = (1 << 12) | (1 << 3);
+        let enemy = 1 << 36;
+        let expected = slider_ray_att(4, &ROOK_DIRS, own, enemy);
+        assert_eq!(get_rook_mv(4, own, enemy), expected);
+        assert_eq!(expected & own, 0);
+    }
+}
@@ -1,4 +1,85 @@
-use std::arch::x86_64::{_pdep_u64, _pext_u64};
+use std::sync::LazyLock;
+
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::{__cpuid, _pdep_u64, _pext_u64};
+
+#[cfg(all(feature = "pext", feature = "magic"))]
+compile_error!("The `pext` and `magic` features are mutually exclusive");
+
+#[cfg(all(feature = "pext", not(target_arch = "x86_64")))]
+compile_error!("The `pext` feature needs BMI2, which is only available on x86_64");
+
+/// Lookup scheme used by the rook and bishop attack functions.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SliderBackend {
+    /// BMI2 `PEXT` indexing. Fast on Intel and AMD Zen 3+, microcoded on older AMD chips.
+    Pext,
+    /// Portable multiply-and-shift (fancy magic) indexing.
+    Magic,
+}
+
+/// Picks the slider backend. The `pext` and `magic` cargo features pin it at compile time,
+/// otherwise it is detected once, the first time a slider attack is looked up.
+#[inline(always)]
+pub fn slider_backend() -> SliderBackend {
+    if cfg!(feature = "magic") {
+        return SliderBackend::Magic;
+    }
+
+    if cfg!(feature = "pext") {
+        return SliderBackend::Pext;
+    }
+
+    *DETECTED_BACKEND
+}
+
+static DETECTED_BACKEND: LazyLock<SliderBackend> = LazyLock::new(detect_backend);
+
+/// `PEXT` when the CPU reports BMI2 and doesn't run it in microcode.
+fn detect_backend() -> SliderBackend {
+    #[cfg(target_arch = "x86_64")]
+    if std::arch::is_x86_feature_detected!("bmi2")
+        && !pext_is_microcoded(cpu_vendor(), cpu_family())
+    {
+        return SliderBackend::Pext;
+    }
+
+    SliderBackend::Magic
+}
+
+/// AMD before Zen 3 (family 0x19) reports BMI2, but `PEXT` takes hundreds of cycles there.
+#[cfg(target_arch = "x86_64")]
+fn pext_is_microcoded(vendor: [u8; 12], family: u32) -> bool {
+    &vendor == b"AuthenticAMD" && family < ZEN3_FAMILY
+}
+
+#[cfg(target_arch = "x86_64")]
+const ZEN3_FAMILY: u32 = 0x19;
+
+#[cfg(target_arch = "x86_64")]
+fn cpu_vendor() -> [u8; 12] {
+    let leaf = __cpuid(0);
+    let mut vendor = [0; 12];
+    vendor[..4].copy_from_slice(&leaf.ebx.to_le_bytes());
+    vendor[4..8].copy_from_slice(&leaf.edx.to_le_bytes());
+    vendor[8..].copy_from_slice(&leaf.ecx.to_le_bytes());
+    vendor
+}
+
+#[cfg(target_arch = "x86_64")]
+fn cpu_family() -> u32 {
+    family_from_signature(__cpuid(1).eax)
+}
+
+/// The extended family only counts when the base family is 0xF.
+#[cfg(target_arch = "x86_64")]
+fn family_from_signature(eax: u32) -> u32 {
+    let base = (eax >> 8) & 0xF;
+    match base {
+        0xF => base + ((eax >> 20) & 0xFF),
+        _ => base,
+    }
+}
 
 #[inline(always)]
 pub fn insert_bits(mask: u64, occupancy: u64) -> u64 {
@@ -16,12 +97,75 @@ pub fn insert_bits(mask: u64, occupancy: u64) -> u64 {
     result
 }
 
+#[inline(always)]
+pub fn extract_bits(bitboard: u64, mut mask: u64) -> u64 {
+    let mut result = 0;
+    let mut bit = 0;
+    while mask != 0 {
+        let i = mask.trailing_zeros();
+        if (bitboard >> i) & 1 == 1 {
+            result |= 1 << bit;
+        }
+        mask &= mask - 1;
+        bit += 1;
+    }
+
+    result
+}
+
+#[cfg(target_arch = "x86_64")]
 #[inline]
 pub fn pext(bitboard: u64, mask: u64) -> u64 {
     unsafe { _pext_u64(bitboard, mask) }
 }
 
+#[cfg(not(target_arch = "x86_64"))]
+#[inline]
+pub fn pext(bitboard: u64, mask: u64) -> u64 {
+    extract_bits(bitboard, mask)
+}
+
+#[cfg(target_arch = "x86_64")]
 #[inline(always)]
 pub fn pdep(bitboard: u64, mask: u64) -> u64 {
     unsafe { _pdep_u64(bitboard, mask) }
 }
+
+#[cfg(not(target_arch = "x86_64"))]
+#[inline(always)]
+pub fn pdep(bitboard: u64, mask: u64) -> u64 {
+    insert_bits(mask, bitboard)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_microcoded_pext() {
+        // Zen 2 (family 0x17) and Zen 3 (0x19), signatures as CPUID leaf 1 reports them
+        let (zen2, zen3) = (family_from_signature(0x0087_0F10), family_from_signature(0x00A2_0F10));
+        assert_eq!((zen2, zen3), (0x17, 0x19));
+        assert!(pext_is_microcoded(*b"AuthenticAMD", zen2));
+        assert!(!pext_is_microcoded(*b"AuthenticAMD", zen3));
+
+        // Intel family 6, the extended family is ignored
+        assert_eq!(family_from_signature(0x0009_06EA), 6);
+        assert!(!pext_is_microcoded(*b"GenuineIntel", 6));
+    }
+
+    #[test]
+    fn test_extract_insert_bits() {
+        let mask = 0x0001_0101_0101_017E;
+        let mut occupancy = 0u64;
+        loop {
+            let key = extract_bits(occupancy, mask);
+            assert_eq!(insert_bits(mask, key), occupancy);
+            occupancy = occupancy.wrapping_sub(mask) & mask;
+            if occupancy == 0 {
+                break;
+            }
+        }
+    }
+}
@@ -1 +1,2 @@
 pub mod evaluation;
+pub mod see;
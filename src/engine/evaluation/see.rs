@@ -0,0 +1,164 @@
+use crate::engine::game::Game;
+use crate::engine::move_generation::mv_gen::attackers_to;
+use crate::engine::shared::helper_func::bitboard::BitboardTrait;
+use crate::engine::shared::structures::color::*;
+use crate::engine::shared::structures::internal_move::*;
+use crate::engine::shared::structures::piece::*;
+
+// Least valuable attacker first
+const SEE_ORDER: [Piece; 6] = [PAWN, KNIGHT, BISHOP, ROOK, QUEEN, KING];
+
+/// Static Exchange Evaluation: the material won (or lost) by the side making `rev` when both
+/// sides keep recapturing on the target square with their least valuable attacker and may stop
+/// whenever continuing would lose material. Attackers hidden behind the pieces that already
+/// captured (x-rays) join the exchange, since the occupancy is updated after every capture.
+pub fn see(game: &Game, rev: &PositionRev) -> isize {
+    let to = rev.to as usize;
+    let mut occ = (game.bitboard(WHITE) | game.bitboard(BLACK)) & !(1 << rev.from);
+
+    let mut gain = [0isize; 32];
+    let mut on_square = rev.piece.weight();
+    match rev.flag {
+        Flag::KingCastle | Flag::QueenCastle => return 0,
+        Flag::Capture(cap) => gain[0] = cap.weight(),
+        Flag::EP => {
+            gain[0] = PAWN.weight();
+            occ &= !(1 << (rev.to + 16 * rev.piece.color() - 8));
+        }
+        Flag::Promotion(promo, cap) => {
+            gain[0] = cap.map_or(0, |cap| cap.weight()) + promo.weight() - PAWN.weight();
+            on_square = promo.weight();
+        }
+        Flag::Quiet => (),
+    }
+
+    let mut color = rev.piece.color().opp();
+    let mut depth = 0;
+    loop {
+        let attackers = attackers_to(game, to, occ);
+        let own = attackers & game.bitboard(color);
+        if own == 0 {
+            break;
+        }
+
+        let (piece, from) = match SEE_ORDER.iter().find_map(|&piece| {
+            let bb = own & game.bitboard(piece + color);
+            (bb != 0).then(|| (piece, bb.get_lsb()))
+        }) {
+            Some(attacker) => attacker,
+            None => break,
+        };
+
+        // The king can only take when the square is no longer defended
+        if piece == KING && attackers & game.bitboard(color.opp()) != 0 {
+            break;
+        }
+
+        depth += 1;
+        // A pawn recapturing on the last rank promotes
+        match piece == PAWN && !(8..56).contains(&to) {
+            true => {
+                gain[depth] = on_square + QUEEN.weight() - PAWN.weight() - gain[depth - 1];
+                on_square = QUEEN.weight();
+            }
+            false => {
+                gain[depth] = on_square - gain[depth - 1];
+                on_square = piece.weight();
+            }
+        }
+        occ &= !(1 << from);
+        color = color.opp();
+    }
+
+    // Walk back through the exchange, each side only continues if it doesn't lose material
+    while depth > 0 {
+        gain[depth - 1] = -(-gain[depth - 1]).max(gain[depth]);
+        depth -= 1;
+    }
+
+    gain[0]
+}
+
+/// Checks if the exchange started by `rev` wins at least `threshold`.
+#[inline(always)]
+pub fn see_ge(game: &Game, rev: &PositionRev, threshold: isize) -> bool {
+    see(game, rev) >= threshold
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::fen::fen::FenTrait;
+    use crate::engine::shared::helper_func::print_utility::from_move_notation;
+
+    const P: isize = 100;
+    const N: isize = 325;
+    const B: isize = 350;
+    const R: isize = 500;
+    const Q: isize = 900;
+
+    fn see_of(fen: &str, mv: &str) -> isize {
+        let game = Game::read_fen(fen);
+        let (_, rev) = from_move_notation(mv, &game);
+        see(&game, &rev)
+    }
+
+    #[test]
+    fn test_see_positions() {
+        let positions = [
+            ("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1", "e1e5", P),
+            ("1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1", "d3e5", P - N),
+            ("6k1/1pp4p/p1pb4/6q1/3P1pRr/2P4P/PP1Br1P1/5RKN w - - 0 1", "f1f4", P - R + B),
+            ("5rk1/1pp2q1p/p1pb4/8/3P1NP1/2P5/1P1BQ1P1/5RK1 b - - 0 1", "d6f4", N - B),
+            ("4R3/2r3p1/5bk1/1p1r3p/p2PR1P1/P1BK1P2/1P6/8 b - - 0 1", "h5g4", 0),
+            ("4R3/2r3p1/5bk1/1p1r1p1p/p2PR1P1/P1BK1P2/1P6/8 b - - 0 1", "h5g4", 0),
+            ("4r1k1/5pp1/nbp4p/1p2p2q/1P2P1b1/1BP2N1P/1B2QPPK/3R4 b - - 0 1", "g4f3", N - B),
+            ("2r1r1k1/pp1bppbp/3p1np1/q3P3/2P2P2/1P2B3/P1N1B1PP/2RQ1RK1 b - - 0 1", "d6e5", P),
+            ("7r/5qpk/p1Qp1b1p/3r3n/BB3p2/5p2/P1P2P2/4RK1R w - - 0 1", "e1e8", 0),
+            ("6rr/6pk/p1Qp1b1p/2n5/1B3p2/5p2/P1P2P2/4RK1R w - - 0 1", "e1e8", -R),
+            ("7r/5qpk/2Qp1b1p/1N1r3n/BB3p2/5p2/P1P2P2/4RK1R w - - 0 1", "e1e8", -R),
+            ("6RR/4bP2/8/8/5r2/3K4/5p2/4k3 w - - 0 1", "f7f8q", B - P),
+            ("6RR/4bP2/8/8/5r2/3K4/5p2/4k3 w - - 0 1", "f7f8n", N - P),
+            ("7R/5P2/8/8/6r1/3K4/5p2/4k3 w - - 0 1", "f7f8q", Q - P),
+            ("7R/5P2/8/8/6r1/3K4/5p2/4k3 w - - 0 1", "f7f8b", B - P),
+            ("7R/4bP2/8/8/1q6/3K4/5p2/4k3 w - - 0 1", "f7f8r", -P),
+            ("8/4kp2/2npp3/1Nn5/1p2PQP1/7q/1PP1B3/4KR1r b - - 0 1", "h1f1", 0),
+            ("8/4kp2/2npp3/1Nn5/1p2P1P1/7q/1PP1B3/4KR1r b - - 0 1", "h1f1", 0),
+            ("2r2r1k/6bp/p7/2q2p1Q/3PpP2/1B6/P5PP/2RR3K b - - 0 1", "c5c1", 2 * R - Q),
+            ("r2qk1nr/pp2ppbp/2b3p1/2p1p3/8/2N2N2/PPPP1PPP/R1BQR1K1 w kq - 0 1", "f3e5", P),
+            ("6r1/4kq2/b2p1p2/p1pPb3/p1P2B1Q/2P4P/2B1R1P1/6K1 w - - 0 1", "f4e5", 0),
+            ("3q2nk/pb1r1p2/np6/3P2Pp/2p1P3/2R4B/PQ3P1P/3R2K1 w - h6 0 1", "g5h6", 0),
+            ("3q2nk/pb1r1p2/np6/3P2Pp/2p1P3/2R1B2B/PQ3P1P/3R2K1 w - h6 0 1", "g5h6", P),
+            ("2r4r/1P4pk/p2p1b1p/7n/BB3p2/2R2p2/P1P2P2/4RK2 w - - 0 1", "c3c8", R),
+            ("2r5/1P4pk/p2p1b1p/5b1n/BB3p2/2R2p2/P1P2P2/4RK2 w - - 0 1", "c3c8", R),
+            ("2r4k/2r4p/p7/2b2p1b/4pP2/1BR5/P1R3PP/2Q4K w - - 0 1", "c3c5", B),
+            ("8/pp6/2pkp3/4bp2/2R3b1/2P5/PP4B1/1K6 w - - 0 1", "g2c6", P - B),
+            ("4q3/1p1pr1k1/1B2rp2/6p1/p3PP2/P3R1P1/1P2R1K1/4Q3 b - - 0 1", "e6e4", P - R),
+            ("4q3/1p1pr1kb/1B2rp2/6p1/p3PP2/P3R1P1/1P2R1K1/4Q3 b - - 0 1", "h7e4", P),
+            ("1k1r4/1ppn3p/p4b2/4n3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1", "d3e5", N - N + B - R + N),
+            ("r2q1rk1/2p1bppp/p2p1n2/1p2P3/4P1b1/1nP1BN2/PP3PPP/RN1QR1K1 b - - 0 1", "g4f3", N - B),
+            (
+                "r1bqkb1r/2pp1ppp/p1n5/1p2p3/3Pn3/1B3N2/PPP2PPP/RNBQ1RK1 b kq - 0 1",
+                "c6d4",
+                P - N + N - P,
+            ),
+            ("r1bq1r2/pp1ppkbp/4N1p1/n3P1B1/8/2N5/PPP2PPP/R2QK2R w KQ - 0 1", "e6g7", B - N),
+            ("r1bq1r2/pp1ppkbp/4N1pB/n3P3/8/2N5/PPP2PPP/R2QK2R w KQ - 0 1", "e6g7", B),
+        ];
+
+        for (fen, mv, expected) in positions {
+            assert_eq!(see_of(fen, mv), expected, "{} {}", fen, mv);
+        }
+    }
+
+    #[test]
+    fn test_see_ge() {
+        let fen = "1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1";
+        let game = Game::read_fen(fen);
+        let (_, rev) = from_move_notation("d3e5", &game);
+
+        assert!(see_ge(&game, &rev, P - N));
+        assert!(!see_ge(&game, &rev, P - N + 1));
+        assert!(!see_ge(&game, &rev, 0));
+    }
+}
@@ -73,16 +73,45 @@ impl FenTrait for Game {
         }
     }
 
+    /// Reads the standard `KQkq` field as well as the Chess960 X-FEN and Shredder-FEN ones.
+    /// `K`/`Q` pick the outermost rook on that side of the king, a file letter names the rook
+    /// directly. Has to run after the pieces are placed.
     fn set_castling(&mut self, castling: &str) {
         for ch in castling.chars() {
-            match ch {
-                'K' => self.castling.add(CastlingRights::WKINGSIDE),
-                'Q' => self.castling.add(CastlingRights::WQUEENSIDE),
-                'k' => self.castling.add(CastlingRights::BKINGSIDE),
-                'q' => self.castling.add(CastlingRights::BQUEENSIDE),
-                '-' => (),
-                _ => panic!("Unknown Castling Rights: {}", ch),
+            if ch == '-' {
+                continue;
             }
+
+            let color = match ch.is_ascii_uppercase() {
+                true => WHITE,
+                false => BLACK,
+            };
+            let king_sq = self.bitboard(KING + color).get_lsb();
+            let back_rank = 0xFFu64 << (56 * color as usize);
+            let rooks = self.bitboard(ROOK + color) & back_rank;
+            let below_king = (1u64 << king_sq) - 1;
+
+            let rook_sq = match ch.to_ascii_lowercase() {
+                'k' => match rooks & !below_king & !(1 << king_sq) {
+                    0 => panic!("No king side rook for castling rights: {}", ch),
+                    bb => bb.get_msb(),
+                },
+                'q' => match rooks & below_king {
+                    0 => panic!("No queen side rook for castling rights: {}", ch),
+                    bb => bb.get_lsb(),
+                },
+                file @ 'a'..='h' => back_rank.get_lsb() + (file as u8 - b'a') as usize,
+                _ => panic!("Unknown Castling Rights: {}", ch),
+            };
+
+            let side = match rook_sq > king_sq {
+                true => KING_SIDE,
+                false => QUEEN_SIDE,
+            };
+
+            self.castling_sq.king[color.idx()] = king_sq;
+            self.castling_sq.rook[color.idx()][side] = rook_sq;
+            self.castling.add(CastlingRights::from_side(color, side));
         }
     }
 
@@ -145,6 +174,26 @@ mod tests {
         game.bitboard[WHITE_PAWN.idx()].print(None);
     }
 
+    #[test]
+    fn test_fen_chess960_castling() {
+        let shredder =
+            Game::read_fen("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9");
+        let x_fen =
+            Game::read_fen("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w KQkq - 2 9");
+
+        assert_eq!(shredder.castling, CastlingRights::ALL);
+        assert_eq!(shredder.castling_sq.king, [SqPos::G1 as usize, SqPos::G8 as usize]);
+        assert_eq!(
+            shredder.castling_sq.rook,
+            [[SqPos::H1 as usize, SqPos::F1 as usize], [SqPos::H8 as usize, SqPos::F8 as usize]]
+        );
+        assert_eq!(x_fen.castling_sq, shredder.castling_sq);
+        assert_eq!(x_fen.key, shredder.key);
+
+        let standard = Game::read_fen(FEN_START);
+        assert!(standard.castling_sq.is_standard());
+    }
+
     #[test]
     fn test_occupancy_start_position() {
         let game = Game::initialize();
@@ -1,13 +1,15 @@
 use super::fen::fen::FenTrait;
+use super::search::history::History;
+use super::search::pv_table::PvTable;
 use super::search::searcher::SearchInfo;
-use super::search::transposition_table::TTTable;
+use super::search::searcher::MAX_PLY;
 use super::shared::helper_func::bitboard::*;
 use super::shared::helper_func::const_utility::*;
 use super::shared::structures::color::*;
 use super::shared::structures::internal_move::PositionIrr;
 use super::shared::structures::internal_move::PositionRev;
 use super::shared::structures::piece::Piece;
-use crate::engine::shared::structures::castling_struct::CastlingRights;
+use crate::engine::shared::structures::castling_struct::{CastlingRights, CastlingSquares};
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Game {
@@ -29,15 +31,25 @@ pub struct Game {
     pub half_move: u8,
     pub full_move: u16,
 
+    // Castling start squares (they differ from the standard ones in Chess960)
+    pub castling_sq: CastlingSquares,
+    // Write castling as king takes rook (UCI_Chess960)
+    pub chess960: bool,
+
     // Moves Played from the position that is on the board.
     pub ply: usize,
 
-    // Transposition Table
-    pub tt: TTTable,
-
     // Move Ordering Technics
-    pub s_history: [[u64; 64]; 14],
-    pub s_killers: [[Option<PositionRev>; 2]; 64],
+    pub s_history: History,
+    pub s_killers: [[Option<PositionRev>; 2]; MAX_PLY],
+    pub s_pv: PvTable,
+    // Move played at each ply of the search (None for a null move), the context of the
+    // counter move and continuation history
+    pub s_moves: [Option<PositionRev>; MAX_PLY],
+    // Move left out by the singular extension search at each ply
+    pub s_excluded: [Option<PositionRev>; MAX_PLY],
+    // Extensions on the path from the root to each ply
+    pub s_extensions: [u8; MAX_PLY],
 
     // Search Info and UCI commands FIXME: Split maybe in two structs
     pub info: SearchInfo,
@@ -58,12 +70,17 @@ impl Game {
             half_move: 0,
             full_move: 1,
             key: 0,
+            castling_sq: CastlingSquares::STANDARD,
+            chess960: false,
 
             pos_rev: Vec::with_capacity(1024),
             pos_irr: Vec::with_capacity(1024),
-            tt: TTTable::init(),
-            s_history: [[0u64; 64]; 14],
-            s_killers: [[None; 2]; 64],
+            s_history: History::init(),
+            s_killers: [[None; 2]; MAX_PLY],
+            s_pv: PvTable::init(),
+            s_moves: [None; MAX_PLY],
+            s_excluded: [None; MAX_PLY],
+            s_extensions: [0; MAX_PLY],
             ply: 0,
             info: SearchInfo::init(),
         }
@@ -77,9 +94,9 @@ impl Game {
         self.ep = None;
         self.half_move = 0;
         self.full_move = 1;
+        self.castling_sq = CastlingSquares::STANDARD;
         self.pos_rev = Vec::with_capacity(1024);
         self.pos_irr = Vec::with_capacity(1024);
-        self.tt = TTTable::init();
         self.info = SearchInfo::init();
     }
 
@@ -134,6 +151,7 @@ mod tests {
         assert_eq!(game.ep, None);
         assert_eq!(game.half_move, 0);
         assert_eq!(game.full_move, 1);
+        assert_eq!(game.castling_sq, CastlingSquares::STANDARD);
         assert_eq!(game.pos_rev.len(), 0);
         assert_eq!(game.pos_irr.len(), 0);
     }
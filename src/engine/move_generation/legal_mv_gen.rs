@@ -0,0 +1,460 @@
+use crate::engine::attacks::bishop::*;
+use crate::engine::attacks::generated::king::KING_LOOKUP;
+use crate::engine::attacks::generated::pawn::PAWN_ATTACK_LOOKUP;
+use crate::engine::attacks::generated::rays::RAYS_LOOKUP;
+use crate::engine::attacks::knight::*;
+use crate::engine::attacks::pawn::*;
+use crate::engine::attacks::queen::*;
+use crate::engine::attacks::rook::*;
+use crate::engine::game::Game;
+use crate::engine::shared::helper_func::bit_pos_utility::get_bit_rank;
+use crate::engine::shared::helper_func::bitboard::BitboardTrait;
+use crate::engine::shared::helper_func::bitboard::Iterator;
+use crate::engine::shared::helper_func::const_utility::Rank;
+use crate::engine::shared::structures::color::*;
+use crate::engine::shared::structures::directions::DIRECTIONS;
+use crate::engine::shared::structures::internal_move::*;
+use crate::engine::shared::structures::piece::*;
+
+use super::mv_gen::{add_castling_moves, attackers_to, get_occupancy, get_positions_rev};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum GenType {
+    All,
+    Captures,
+    Quiets,
+    // Quiet moves that check the enemy king, directly or by uncovering a slider (no castling)
+    QuietChecks,
+}
+
+/// Check and pin information of the side to move, computed once per position so that only
+/// legal moves get generated.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct LegalMasks {
+    pub king_sq: usize,
+    pub checkers: u64,
+    pub check_mask: u64,
+    pub pinned: u64,
+}
+
+impl LegalMasks {
+    pub fn init(game: &Game) -> Self {
+        let color = game.color;
+        let (own, enemy) = get_occupancy(&color, game);
+        let king_sq = game.bitboard(KING + color).get_lsb();
+
+        let checkers = attackers_to(game, king_sq, own | enemy) & enemy;
+        let check_mask = match checkers.count() {
+            0 => u64::MAX,
+            1 => between(king_sq, checkers.get_lsb()) | checkers,
+            _ => 0,
+        };
+
+        // Enemy sliders that would hit the king if our own pieces were not there
+        let enemy_rq = game.bitboard(ROOK + color.opp()) | game.bitboard(QUEEN + color.opp());
+        let enemy_bq = game.bitboard(BISHOP + color.opp()) | game.bitboard(QUEEN + color.opp());
+        let mut snipers = (get_rook_mv(king_sq, 0, enemy) & enemy_rq)
+            | (get_bishop_mv(king_sq, 0, enemy) & enemy_bq);
+
+        let mut pinned = 0;
+        while let Some(sniper) = snipers.next() {
+            let blockers = between(king_sq, sniper) & (own | enemy);
+            if blockers.count() == 1 && blockers & own != 0 {
+                pinned |= blockers;
+            }
+        }
+
+        Self { king_sq, checkers, check_mask, pinned }
+    }
+
+    #[inline(always)]
+    pub fn in_check(&self) -> bool {
+        self.checkers != 0
+    }
+
+    /// Squares a non-king piece standing on `sq` may move to.
+    #[inline(always)]
+    pub fn target_mask(&self, sq: usize) -> u64 {
+        match self.pinned.is_set(sq) {
+            true => self.check_mask & pin_ray(self.king_sq, sq),
+            false => self.check_mask,
+        }
+    }
+}
+
+/// Squares strictly between two squares that share a rank, file or diagonal.
+#[inline(always)]
+pub fn between(from: usize, to: usize) -> u64 {
+    for dir in &DIRECTIONS {
+        let ray = RAYS_LOOKUP[dir.idx()][from];
+        if ray.is_set(to) {
+            return ray & !RAYS_LOOKUP[dir.idx()][to] & !(1 << to);
+        }
+    }
+    0
+}
+
+/// Ray starting at the king that goes through the pinned piece on `sq`.
+#[inline(always)]
+fn pin_ray(king_sq: usize, sq: usize) -> u64 {
+    for dir in &DIRECTIONS {
+        let ray = RAYS_LOOKUP[dir.idx()][king_sq];
+        if ray.is_set(sq) {
+            return ray;
+        }
+    }
+    0
+}
+
+#[inline(always)]
+pub fn gen_legal_moves(game: &Game) -> (PositionIrr, Vec<PositionRev>) {
+    let position_irr = PositionIrr::init_with_game(game);
+    let masks = LegalMasks::init(game);
+
+    let mut positions_rev: Vec<PositionRev> = Vec::with_capacity(256);
+    add_legal_moves(game, &masks, GenType::All, &mut positions_rev);
+
+    (position_irr, positions_rev)
+}
+
+/// Adds the legal moves of the side to move, restricted to the requested kind of moves.
+pub fn add_legal_moves(
+    game: &Game,
+    masks: &LegalMasks,
+    gen_type: GenType,
+    positions_rev: &mut Vec<PositionRev>,
+) {
+    let color = game.color;
+    let (own, enemy) = get_occupancy(&color, game);
+    let type_mask = match gen_type {
+        GenType::All => !own,
+        GenType::Captures => enemy,
+        GenType::Quiets | GenType::QuietChecks => !(own | enemy),
+    };
+    let checks = (gen_type == GenType::QuietChecks).then(|| CheckSquares::init(game, own, enemy));
+    let check_mask = |piece: Piece, sq: usize| checks.map_or(u64::MAX, |c| c.targets(piece, sq));
+
+    let king = KING + color;
+    let king_targets = safe_king_targets(game, masks.king_sq, own, enemy)
+        & type_mask
+        & check_mask(king, masks.king_sq);
+    get_positions_rev(king_targets, &king, masks.king_sq, game, positions_rev);
+
+    if matches!(gen_type, GenType::All | GenType::Quiets) && !masks.in_check() {
+        add_castling_moves(&king, game, positions_rev);
+    }
+
+    // Only the king can move out of a double check
+    if masks.checkers.count() > 1 {
+        return;
+    }
+
+    for piece in &[PAWN, KNIGHT, BISHOP, ROOK, QUEEN] {
+        let piece = piece + color;
+        let mut bb = game.bitboard(piece);
+        while let Some(sq) = bb.next() {
+            let targets = piece_targets(piece, sq, own, enemy) & masks.target_mask(sq);
+            let targets = targets & type_mask & check_mask(piece, sq);
+            get_positions_rev(targets, &piece, sq, game, positions_rev);
+
+            if piece.is_pawn() && matches!(gen_type, GenType::All | GenType::Captures) {
+                if let Some(ep) = legal_ep(game, masks, sq, own | enemy) {
+                    get_positions_rev(1 << ep, &piece, sq, game, positions_rev);
+                }
+            }
+        }
+    }
+}
+
+/// The squares the pieces of the side to move give check from, and the pieces that give a
+/// discovered check by leaving the line between one of our sliders and the enemy king.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct CheckSquares {
+    king_sq: usize,
+    pawn: u64,
+    knight: u64,
+    bishop: u64,
+    rook: u64,
+    discoverers: u64,
+}
+
+impl CheckSquares {
+    fn init(game: &Game, own: u64, enemy: u64) -> Self {
+        let color = game.color;
+        let occ = own | enemy;
+        let king_sq = game.bitboard(KING + color.opp()).get_lsb();
+
+        let own_rq = game.bitboard(ROOK + color) | game.bitboard(QUEEN + color);
+        let own_bq = game.bitboard(BISHOP + color) | game.bitboard(QUEEN + color);
+        let mut snipers =
+            (get_rook_mv(king_sq, 0, 0) & own_rq) | (get_bishop_mv(king_sq, 0, 0) & own_bq);
+
+        let mut discoverers = 0;
+        while let Some(sniper) = snipers.next() {
+            let blockers = between(king_sq, sniper) & occ;
+            if blockers.count() == 1 && blockers & own != 0 {
+                discoverers |= blockers;
+            }
+        }
+
+        Self {
+            king_sq,
+            pawn: PAWN_ATTACK_LOOKUP[color.opp().idx()][king_sq],
+            knight: get_knight_mv(king_sq, 0, 0),
+            bishop: get_bishop_mv(king_sq, 0, occ),
+            rook: get_rook_mv(king_sq, 0, occ),
+            discoverers,
+        }
+    }
+
+    /// Targets of the piece on `sq` that give check. A discoverer checks anywhere off the line.
+    #[inline(always)]
+    fn targets(&self, piece: Piece, sq: usize) -> u64 {
+        let direct = match piece.kind() {
+            PAWN => self.pawn,
+            KNIGHT => self.knight,
+            BISHOP => self.bishop,
+            ROOK => self.rook,
+            QUEEN => self.bishop | self.rook,
+            _ => 0,
+        };
+        match self.discoverers.is_set(sq) {
+            true => direct | !pin_ray(self.king_sq, sq),
+            false => direct,
+        }
+    }
+}
+
+/// Counts the legal moves without building them, used for bulk counting perft leaves.
+pub fn legal_move_count(game: &Game) -> u64 {
+    let color = game.color;
+    let masks = LegalMasks::init(game);
+    let (own, enemy) = get_occupancy(&color, game);
+
+    let mut count = safe_king_targets(game, masks.king_sq, own, enemy).count() as u64;
+
+    if !masks.in_check() {
+        let mut castles = Vec::with_capacity(2);
+        add_castling_moves(&(KING + color), game, &mut castles);
+        count += castles.len() as u64;
+    }
+
+    if masks.checkers.count() > 1 {
+        return count;
+    }
+
+    // A pawn standing on this rank promotes with every move
+    let promo_rank = match color {
+        WHITE => Rank::Seven,
+        _ => Rank::Two,
+    };
+
+    for piece in &[PAWN, KNIGHT, BISHOP, ROOK, QUEEN] {
+        let piece = piece + color;
+        let mut bb = game.bitboard(piece);
+        while let Some(sq) = bb.next() {
+            let targets = piece_targets(piece, sq, own, enemy) & masks.target_mask(sq);
+            match piece.is_pawn() && get_bit_rank(sq) == promo_rank {
+                true => count += 4 * targets.count() as u64,
+                false => count += targets.count() as u64,
+            }
+
+            if piece.is_pawn() && legal_ep(game, &masks, sq, own | enemy).is_some() {
+                count += 1;
+            }
+        }
+    }
+
+    count
+}
+
+/// Checks if a pseudo-legal move (for example the TT move) is also legal.
+pub fn is_legal(game: &Game, masks: &LegalMasks, rev: &PositionRev) -> bool {
+    let (own, enemy) = get_occupancy(&game.color, game);
+
+    match rev.flag {
+        Flag::KingCastle | Flag::QueenCastle => !masks.in_check(),
+        Flag::EP => legal_ep(game, masks, rev.from as usize, own | enemy).is_some(),
+        _ if rev.piece.is_king() => {
+            safe_king_targets(game, masks.king_sq, own, enemy).is_set(rev.to as usize)
+        }
+        _ => {
+            masks.checkers.count() < 2
+                && masks.target_mask(rev.from as usize).is_set(rev.to as usize)
+        }
+    }
+}
+
+#[inline(always)]
+fn piece_targets(piece: Piece, sq: usize, own: u64, enemy: u64) -> u64 {
+    match piece.kind() {
+        PAWN => {
+            get_pawn_mv(piece.color(), sq, own, enemy)
+                | get_pawn_att(piece.color(), sq, own, enemy, None)
+        }
+        KNIGHT => get_knight_mv(sq, own, enemy),
+        BISHOP => get_bishop_mv(sq, own, enemy),
+        ROOK => get_rook_mv(sq, own, enemy),
+        QUEEN => get_queen_mv(sq, own, enemy),
+        _ => panic!("Invalid Peace Type: {}", piece),
+    }
+}
+
+/// King moves that do not walk into an attack. The king is taken off the board first, so it
+/// can't hide behind itself from a slider.
+#[inline(always)]
+fn safe_king_targets(game: &Game, king_sq: usize, own: u64, enemy: u64) -> u64 {
+    let occ = (own | enemy) & !(1 << king_sq);
+    let mut targets = KING_LOOKUP[king_sq] & !own;
+    let mut safe = 0;
+
+    while let Some(sq) = targets.next() {
+        if attackers_to(game, sq, occ) & enemy == 0 {
+            safe.set_bit(sq);
+        }
+    }
+
+    safe
+}
+
+/// The en passant square if the pawn on `sq` can legally capture there. The capture removes two
+/// pieces from the same rank, so the resulting position is checked directly.
+#[inline(always)]
+fn legal_ep(game: &Game, masks: &LegalMasks, sq: usize, occ: u64) -> Option<u8> {
+    let ep = game.ep?;
+    let color = game.color;
+    if PAWN_ATTACK_LOOKUP[color.idx()][sq] & get_pawn_ep(color, ep) == 0 {
+        return None;
+    }
+
+    let cap_sq = (ep + 16 * color - 8) as usize;
+    let occ = occ ^ (1 << sq) ^ (1 << ep) ^ (1 << cap_sq);
+    let enemy = game.bitboard(color.opp()) & !(1 << cap_sq);
+
+    match attackers_to(game, masks.king_sq, occ) & enemy {
+        0 => Some(ep),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::fen::fen::FenTrait;
+    use crate::engine::move_generation::make_move::GameMoveTrait;
+    use crate::engine::move_generation::mv_gen::{gen_moves, sq_attack};
+    use crate::engine::shared::helper_func::const_utility::*;
+
+    fn pseudo_legal_filtered(game: &mut Game) -> Vec<PositionRev> {
+        let (irr, pos_rev) = gen_moves(game.color, game);
+        let mut legal = Vec::new();
+        for rev in pos_rev {
+            if game.make_move(&rev, &irr) {
+                game.undo_move();
+                legal.push(rev);
+            }
+        }
+        legal
+    }
+
+    fn sorted(mut moves: Vec<PositionRev>) -> Vec<PositionRev> {
+        moves.sort_by_key(|rev| (rev.from, rev.to, rev.flag.get_promo_piece()));
+        moves
+    }
+
+    #[test]
+    fn test_legal_moves_match_filtered_moves() {
+        let fens = [
+            FEN_START,
+            FEN_POS_TWO,
+            FEN_POS_THREE,
+            FEN_POS_FOUR,
+            FEN_POS_FIVE,
+            FEN_POS_SIX,
+            FEN_PAWNS_WHITE,
+            FEN_PAWNS_BLACK,
+            // En passant would expose the king along the rank
+            "8/8/8/K1pP3r/8/8/8/7k w - c6 0 1",
+            // En passant removes the checking pawn
+            "8/8/8/2k5/3Pp3/8/8/4K3 b - d3 0 1",
+            // Double check
+            "4k3/8/8/8/8/5n2/8/R3K2r w Q - 0 1",
+        ];
+
+        for fen in fens {
+            let mut game = Game::read_fen(fen);
+            let (_, legal) = gen_legal_moves(&game);
+
+            assert_eq!(legal_move_count(&game), legal.len() as u64, "{}", fen);
+            assert_eq!(sorted(legal), sorted(pseudo_legal_filtered(&mut game)), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn test_gen_types_split_moves() {
+        let game = Game::read_fen(FEN_POS_TWO);
+        let masks = LegalMasks::init(&game);
+        let (_, all) = gen_legal_moves(&game);
+
+        let mut split = Vec::new();
+        add_legal_moves(&game, &masks, GenType::Captures, &mut split);
+        assert!(split.iter().all(|rev| rev.flag.is_capture()));
+        add_legal_moves(&game, &masks, GenType::Quiets, &mut split);
+
+        assert_eq!(sorted(all), sorted(split));
+    }
+
+    #[test]
+    fn test_quiet_checks() {
+        let fens = [
+            FEN_POS_TWO,
+            FEN_POS_THREE,
+            FEN_POS_FOUR,
+            FEN_POS_FIVE,
+            FEN_POS_SIX,
+            // The knight and the bishop uncover the rook and the queen
+            "4k3/8/8/8/4N3/8/1B6/Q3R1K1 w - - 0 1",
+            // The pawn push uncovers the rook along the rank
+            "8/8/8/R2P3k/8/8/8/6K1 w - - 0 1",
+        ];
+
+        for fen in fens {
+            let mut game = Game::read_fen(fen);
+            let masks = LegalMasks::init(&game);
+            let mut checks = Vec::new();
+            add_legal_moves(&game, &masks, GenType::QuietChecks, &mut checks);
+
+            let mut quiets = Vec::new();
+            add_legal_moves(&game, &masks, GenType::Quiets, &mut quiets);
+
+            let irr = PositionIrr::init_with_game(&game);
+            let mut gives_check = |rev: &PositionRev| {
+                game.make_legal_move(rev, &irr);
+                let king_sq = game.bitboard(KING + game.color).get_lsb();
+                let check = sq_attack(&game, king_sq, game.color) != 0;
+                game.undo_move();
+                check
+            };
+            assert!(checks.iter().all(|rev| !rev.flag.is_capture() && gives_check(rev)), "{}", fen);
+
+            // Every quiet check is there, except castling and the checks of a promoted piece
+            let expected = quiets.into_iter().filter(|rev| {
+                !matches!(rev.flag, Flag::KingCastle | Flag::QueenCastle | Flag::Promotion(..))
+                    && gives_check(rev)
+            });
+            let mut missing = expected.filter(|rev| !checks.contains(rev));
+            assert_eq!(missing.next(), None, "{}", fen);
+        }
+    }
+
+    #[test]
+    fn test_ep_discovered_check() {
+        let game = Game::read_fen("8/8/8/K1pP3r/8/8/8/7k w - c6 0 1");
+        let (_, legal) = gen_legal_moves(&game);
+        assert!(legal.iter().all(|rev| rev.flag != Flag::EP));
+
+        let game = Game::read_fen("8/8/8/2k5/3Pp3/8/8/4K3 b - d3 0 1");
+        let (_, legal) = gen_legal_moves(&game);
+        assert!(legal.iter().any(|rev| rev.flag == Flag::EP));
+    }
+}
@@ -3,9 +3,8 @@ use crate::engine::game::Game;
 use crate::engine::shared::helper_func::bitboard::BitboardTrait;
 use crate::engine::shared::helper_func::print_utility::print_bitboard;
 use crate::engine::shared::helper_func::print_utility::print_chess;
-use crate::engine::shared::structures::castling_struct::CASTLE_DATA;
-use crate::engine::shared::structures::castling_struct::ROOK_SQ;
-use crate::engine::shared::structures::color::ColorTrait;
+use crate::engine::shared::structures::castling_struct::*;
+use crate::engine::shared::structures::color::{ColorTrait, COLORS};
 use crate::engine::shared::structures::internal_move::*;
 use crate::engine::shared::structures::piece::*;
 use core::panic;
@@ -14,6 +13,7 @@ use super::mv_gen::sq_attack;
 
 pub trait GameMoveTrait {
     fn make_move(&mut self, rev: &PositionRev, irr: &PositionIrr) -> bool;
+    fn make_legal_move(&mut self, rev: &PositionRev, irr: &PositionIrr);
     fn undo_move(&mut self);
     fn make_null_move(&mut self) -> bool;
     fn undo_null_move(&mut self) -> bool;
@@ -22,10 +22,25 @@ pub trait GameMoveTrait {
     fn clear_piece(&mut self, sq: usize);
     fn replace_piece(&mut self, from_sq: usize, to_sq: usize);
     fn quiet_mv(&mut self, from_sq: usize, to_sq: usize, piece: Piece);
+    fn castle_mv(&mut self, rev: &PositionRev, side: usize, undo: bool);
 }
 
 impl GameMoveTrait for Game {
     fn make_move(&mut self, rev: &PositionRev, irr: &PositionIrr) -> bool {
+        self.make_legal_move(rev, irr);
+
+        let king_sq = self.bitboard[(KING + rev.piece.color()) as usize].get_lsb();
+
+        if sq_attack(self, king_sq, rev.piece.color()) != 0 {
+            self.undo_move();
+            return false;
+        }
+
+        true
+    }
+
+    /// Plays a move that is already known to be legal, skipping the king safety check.
+    fn make_legal_move(&mut self, rev: &PositionRev, irr: &PositionIrr) {
         match rev.flag {
             Flag::Quiet => self.quiet_mv(rev.from as usize, rev.to as usize, rev.piece),
             Flag::Capture(_) => self.replace_piece(rev.from as usize, rev.to as usize),
@@ -37,28 +52,25 @@ impl GameMoveTrait for Game {
                 self.clear_piece(rev.from as usize);
                 self.add_piece(rev.to as usize, piece);
             }
-            Flag::KingCastle => {
-                let sq = &ROOK_SQ[rev.piece.color().idx()][0];
-                self.quiet_mv(rev.from as usize, rev.to as usize, rev.piece);
-                self.quiet_mv(sq.0, sq.1, ROOK + rev.piece.color());
-            }
-            Flag::QueenCastle => {
-                let sq = &ROOK_SQ[rev.piece.color().idx()][1];
-                self.quiet_mv(rev.from as usize, rev.to as usize, rev.piece);
-                self.quiet_mv(sq.0, sq.1, ROOK + rev.piece.color());
-            }
+            Flag::KingCastle => self.castle_mv(rev, KING_SIDE, false),
+            Flag::QueenCastle => self.castle_mv(rev, QUEEN_SIDE, false),
         }
 
         self.color.change_color();
 
         //If the castleRight is set, and if the king is on place and rook is on place than retain otherwise clear
 
-        for c in &CASTLE_DATA {
-            if !self.castling.is_set(c.2)
-                || !self.bitboard[(ROOK + c.3) as usize].is_set(c.0)
-                || !self.bitboard[(KING + c.3) as usize].is_set(c.1)
-            {
-                self.castling.clear(c.2);
+        for color in COLORS {
+            for side in [KING_SIDE, QUEEN_SIDE] {
+                let castle = CastlingRights::from_side(color, side);
+                if self.castling.is_set(castle)
+                    && (!self.bitboard[(ROOK + color) as usize]
+                        .is_set(self.castling_sq.rook[color.idx()][side])
+                        || !self.bitboard[(KING + color) as usize]
+                            .is_set(self.castling_sq.king[color.idx()]))
+                {
+                    self.castling.clear(castle);
+                }
             }
         }
 
@@ -84,15 +96,6 @@ impl GameMoveTrait for Game {
 
         self.pos_irr.push(*irr);
         self.pos_rev.push(*rev);
-
-        let king_sq = self.bitboard[(KING + rev.piece.color()) as usize].get_lsb();
-
-        if sq_attack(self, king_sq, rev.piece.color()) != 0 {
-            self.undo_move();
-            return false;
-        }
-
-        true
     }
 
     fn undo_move(&mut self) {
@@ -130,19 +133,30 @@ impl GameMoveTrait for Game {
                 }
                 self.add_piece(rev.from as usize, rev.piece);
             }
-            Flag::KingCastle => {
-                let sq = &ROOK_SQ[rev.piece.color().idx()][0];
-                self.quiet_mv(rev.to as usize, rev.from as usize, rev.piece);
-                self.quiet_mv(sq.1, sq.0, ROOK + rev.piece.color());
-            }
-            Flag::QueenCastle => {
-                let sq = &ROOK_SQ[rev.piece.color().idx()][1];
-                self.quiet_mv(rev.to as usize, rev.from as usize, rev.piece);
-                self.quiet_mv(sq.1, sq.0, ROOK + rev.piece.color());
-            }
+            Flag::KingCastle => self.castle_mv(&rev, KING_SIDE, true),
+            Flag::QueenCastle => self.castle_mv(&rev, QUEEN_SIDE, true),
         }
     }
 
+    /// Moves the king and the rook of a castling move (or back when undoing it). In Chess960
+    /// the king or rook may already stand on its destination, or on the other one's, so both
+    /// pieces are lifted before they are placed again.
+    #[inline(always)]
+    fn castle_mv(&mut self, rev: &PositionRev, side: usize, undo: bool) {
+        let color = rev.piece.color();
+        let king = (rev.from as usize, rev.to as usize);
+        let rook = (self.castling_sq.rook[color.idx()][side], CASTLE_ROOK_TO[color.idx()][side]);
+        let (king_from, king_to, rook_from, rook_to) = match undo {
+            false => (king.0, king.1, rook.0, rook.1),
+            true => (king.1, king.0, rook.1, rook.0),
+        };
+
+        self.clear_piece(king_from);
+        self.clear_piece(rook_from);
+        self.add_piece(king_to, rev.piece);
+        self.add_piece(rook_to, ROOK + color);
+    }
+
     #[inline(always)]
     fn quiet_mv(&mut self, from_sq: usize, to_sq: usize, piece: Piece) {
         self.squares[from_sq] = None;
@@ -204,11 +218,75 @@ impl GameMoveTrait for Game {
         }
     }
 
+    /// Passes the turn to the opponent. Not allowed when the side to move is in check.
+    /// The fifty move counter is reset so that repetitions are not detected across the null move.
     fn make_null_move(&mut self) -> bool {
-        todo!()
+        let king_sq = self.bitboard[(KING + self.color) as usize].get_lsb();
+        if sq_attack(self, king_sq, self.color) != 0 {
+            return false;
+        }
+
+        self.pos_irr.push(PositionIrr::init_with_game(self));
+
+        if let Some(idx) = self.ep {
+            self.key ^= EP_KEYS[idx as usize];
+        }
+        self.key ^= SIDE_KEY;
+
+        self.ep = None;
+        self.half_move = 0;
+        self.color.change_color();
+        self.ply += 1;
+
+        true
     }
 
     fn undo_null_move(&mut self) -> bool {
-        todo!()
+        let irr = match self.pos_irr.pop() {
+            Some(irr) => irr,
+            None => return false,
+        };
+
+        self.key = irr.key;
+        self.ep = irr.ep;
+        self.half_move = irr.half_move;
+        self.color.change_color();
+        self.ply -= 1;
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{
+        fen::fen::FenTrait,
+        shared::helper_func::const_utility::{FEN_PAWNS_BLACK, FEN_POS_TWO},
+    };
+
+    #[test]
+    fn test_null_move_restores_position() {
+        for fen in [FEN_POS_TWO, FEN_PAWNS_BLACK] {
+            let mut game = Game::read_fen(fen);
+            let before = game.clone();
+
+            assert!(game.make_null_move());
+            assert_ne!(game.key, before.key);
+            assert_ne!(game.color, before.color);
+            assert_eq!(game.ep, None);
+
+            assert!(game.undo_null_move());
+            assert_eq!(game, before);
+        }
+    }
+
+    #[test]
+    fn test_null_move_in_check() {
+        let mut game = Game::read_fen("4k3/8/8/8/8/8/8/r3K3 w - - 0 1");
+        let before = game.clone();
+
+        assert!(!game.make_null_move());
+        assert_eq!(game, before);
     }
 }
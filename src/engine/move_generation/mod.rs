@@ -1,3 +1,4 @@
+pub mod legal_mv_gen;
 pub mod make_move;
 pub mod mv_gen;
 pub mod perft;
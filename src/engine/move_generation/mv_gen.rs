@@ -13,7 +13,6 @@ use crate::engine::shared::structures::castling_struct::*;
 use crate::engine::shared::structures::color::*;
 use crate::engine::shared::structures::internal_move::*;
 use crate::engine::shared::structures::piece::*;
-use crate::engine::shared::structures::square::SqPos::*;
 
 use super::make_move::GameMoveTrait;
 
@@ -34,23 +33,22 @@ pub fn gen_moves(color: Color, game: &Game) -> (PositionIrr, Vec<PositionRev>) {
 
     add_castling_moves(&(KING + color), game, &mut positions_rev);
 
-    positions_rev.sort_unstable_by(|a, b| eval_pos(b, &game).cmp(&eval_pos(a, &game)));
     (position_irr, positions_rev)
 }
 
 #[inline(always)]
 pub fn gen_captures(color: Color, game: &Game) -> (PositionIrr, Vec<PositionRev>) {
-    let position_irr = PositionIrr {
-        key: game.key,
-        color: game.color,
-        ep: game.ep,
-        castle: game.castling,
-        half_move: game.half_move,
-        full_move: game.full_move,
-        score: 0,
-    };
+    let position_irr = PositionIrr::init_with_game(game);
 
     let mut positions_rev: Vec<PositionRev> = Vec::with_capacity(256);
+    add_captures(color, game, &mut positions_rev);
+
+    (position_irr, positions_rev)
+}
+
+/// Adds every pseudo-legal capture (including en passant and capture promotions).
+#[inline(always)]
+pub fn add_captures(color: Color, game: &Game, positions_rev: &mut Vec<PositionRev>) {
     let (own_occ, enemy_occ) = get_occupancy(&color, game);
 
     for piece in &PIECES {
@@ -65,36 +63,45 @@ pub fn gen_captures(color: Color, game: &Game) -> (PositionIrr, Vec<PositionRev>
                 KING => get_king_mv(sq, own_occ, enemy_occ) & enemy_occ,
                 _ => panic!("Invalid Peace Type"),
             };
-            get_positions_rev(moves, &(piece + color), sq, game, &mut positions_rev);
+            get_positions_rev(moves, &(piece + color), sq, game, positions_rev);
         }
     }
-
-    positions_rev.sort_unstable_by(|a, b| eval_pos(b, &game).cmp(&eval_pos(a, &game)));
-    (position_irr, positions_rev)
 }
 
-fn eval_pos(pos: &PositionRev, game: &Game) -> isize {
-    if matches!(game.tt.get(game.key), Some(x) if x.rev == *pos) {
-        return 95000;
+/// Checks that a move (usually coming from the TT or the killer table) can be played
+/// in the current position, ignoring whether it leaves the own king in check. The flag has to
+/// be the one the move generator would give the move, without generating anything.
+pub fn is_pseudo_legal(game: &Game, rev: &PositionRev) -> bool {
+    if rev.piece.color() != game.color || game.squares[rev.from as usize] != Some(rev.piece) {
+        return false;
     }
 
-    match pos.flag {
-        Flag::Quiet => {
-            if matches!(game.s_killers[game.ply][0], Some(x) if x == *pos) {
-                90000
-            } else if matches!(game.s_killers[game.ply][1], Some(x) if x == *pos) {
-                80000
-            } else {
-                game.s_history[pos.piece.idx()][pos.to as usize] as isize
-            }
+    let (color, to) = (rev.piece.color(), rev.to as usize);
+    let captured = game.squares[to];
+    let promotes = rev.piece.is_pawn() && matches!(get_bit_rank(to), Rank::One | Rank::Eight);
+    let ep = rev.piece.is_pawn() && game.ep == Some(rev.to);
+
+    let flag_fits = match rev.flag {
+        Flag::KingCastle | Flag::QueenCastle => {
+            let side = if rev.flag == Flag::KingCastle { KING_SIDE } else { QUEEN_SIDE };
+            return rev.piece.is_king()
+                && rev.from == game.castling_sq.king[color.idx()] as u8
+                && rev.to == CASTLE_KING_TO[color.idx()][side] as u8
+                && game.castling.valid(color, side, game);
         }
-        Flag::KingCastle => 20,
-        Flag::QueenCastle => 20,
-        Flag::Capture(cap) => cap.weight() - pos.piece as isize,
-        Flag::EP => PAWN.weight(),
-        Flag::Promotion(promo, Some(cap)) => cap.weight() - pos.piece as isize + promo.weight(),
-        Flag::Promotion(promo, None) => promo.weight(),
-    }
+        Flag::Quiet => captured.is_none() && !promotes && !ep,
+        Flag::Capture(piece) => captured == Some(piece) && !promotes,
+        Flag::EP => ep,
+        Flag::Promotion(promo, cap) => {
+            promotes
+                && cap == captured
+                && [QUEEN, ROOK, BISHOP, KNIGHT].iter().any(|kind| kind + color == promo)
+        }
+    };
+
+    let (own_occ, enemy_occ) = get_occupancy(&rev.piece, game);
+    flag_fits
+        && get_all_moves(rev.piece, rev.from as usize, game, own_occ, enemy_occ) & (1 << to) != 0
 }
 
 #[inline(always)]
@@ -135,8 +142,27 @@ pub fn sq_attack(game: &Game, sq: usize, color: Color) -> u64 {
         | (get_king_mv(sq, own_occ, enemy_occ) & op_king)
 }
 
+/// Every piece of both colors attacking `sq`, using `occ` as the board occupancy so that
+/// removed pieces open up x-ray attacks. Pieces not in `occ` are never returned.
 #[inline(always)]
-fn get_positions_rev(
+pub fn attackers_to(game: &Game, sq: usize, occ: u64) -> u64 {
+    let knights = game.bitboard(WHITE_KNIGHT) | game.bitboard(BLACK_KNIGHT);
+    let kings = game.bitboard(WHITE_KING) | game.bitboard(BLACK_KING);
+    let queens = game.bitboard(WHITE_QUEEN) | game.bitboard(BLACK_QUEEN);
+    let rq = game.bitboard(WHITE_ROOK) | game.bitboard(BLACK_ROOK) | queens;
+    let bq = game.bitboard(WHITE_BISHOP) | game.bitboard(BLACK_BISHOP) | queens;
+
+    ((get_pawn_att(WHITE, sq, 0, occ, None) & game.bitboard(BLACK_PAWN))
+        | (get_pawn_att(BLACK, sq, 0, occ, None) & game.bitboard(WHITE_PAWN))
+        | (get_knight_mv(sq, 0, occ) & knights)
+        | (get_bishop_mv(sq, 0, occ) & bq)
+        | (get_rook_mv(sq, 0, occ) & rq)
+        | (get_king_mv(sq, 0, occ) & kings))
+        & occ
+}
+
+#[inline(always)]
+pub fn get_positions_rev(
     mut attacks: u64,
     piece: &Piece,
     from_sq: usize,
@@ -190,25 +216,14 @@ pub fn move_exists(game: &mut Game, rev: &PositionRev) -> bool {
 
 #[inline(always)]
 pub fn add_castling_moves(piece: &Piece, game: &Game, positions: &mut Vec<PositionRev>) {
-    let (own, enemy) = get_occupancy(piece, game);
-    match piece.color() {
-        WHITE => {
-            if game.castling.valid(CastlingRights::WKINGSIDE, game, own, enemy) {
-                positions.push(PositionRev::init(E1 as u8, G1 as u8, *piece, Flag::KingCastle));
-            }
-            if game.castling.valid(CastlingRights::WQUEENSIDE, game, own, enemy) {
-                positions.push(PositionRev::init(E1 as u8, C1 as u8, *piece, Flag::QueenCastle));
-            }
-        }
-        BLACK => {
-            if game.castling.valid(CastlingRights::BKINGSIDE, game, own, enemy) {
-                positions.push(PositionRev::init(E8 as u8, G8 as u8, *piece, Flag::KingCastle));
-            }
-            if game.castling.valid(CastlingRights::BQUEENSIDE, game, own, enemy) {
-                positions.push(PositionRev::init(E8 as u8, C8 as u8, *piece, Flag::QueenCastle));
-            }
+    let color = piece.color();
+    let king_sq = game.castling_sq.king[color.idx()] as u8;
+
+    for (side, flag) in [(KING_SIDE, Flag::KingCastle), (QUEEN_SIDE, Flag::QueenCastle)] {
+        if game.castling.valid(color, side, game) {
+            let king_to = CASTLE_KING_TO[color.idx()][side] as u8;
+            positions.push(PositionRev::init(king_sq, king_to, *piece, flag));
         }
-        _ => panic!("Invalid Castling"),
     }
 }
 
@@ -248,8 +263,11 @@ mod tests {
                 const_utility::{FEN_CASTLE_TWO, FEN_PAWNS_BLACK, FEN_PAWNS_WHITE},
                 print_utility::{print_bitboard, print_chess, print_move_list},
             },
-            structures::piece::{
-                BLACK_QUEEN, WHITE_BISHOP, WHITE_KING, WHITE_KNIGHT, WHITE_QUEEN, WHITE_ROOK,
+            structures::{
+                piece::{
+                    BLACK_QUEEN, WHITE_BISHOP, WHITE_KING, WHITE_KNIGHT, WHITE_QUEEN, WHITE_ROOK,
+                },
+                square::SqPos::*,
             },
         },
     };
@@ -443,6 +461,36 @@ mod tests {
         assert_eq!(test_positions, moves);
     }
 
+    #[test]
+    fn test_is_pseudo_legal() {
+        let fen_ep = "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3";
+        let fens =
+            [FEN_START, FEN2, fen_ep, FEN_CASTLE_ONE, FEN_POS_TWO, FEN_POS_FOUR, FEN_POS_FIVE];
+        let games: Vec<Game> = fens.iter().map(|fen| Game::read_fen(fen)).collect();
+        let moves: Vec<Vec<PositionRev>> =
+            games.iter().map(|game| gen_moves(game.color, game).1).collect();
+        let flags: Vec<Flag> = moves.iter().flatten().map(|rev| rev.flag).collect();
+        assert!(flags.contains(&Flag::EP) && flags.contains(&Flag::QueenCastle));
+        assert!(flags.iter().any(|flag| matches!(flag, Flag::Promotion(_, Some(_)))));
+
+        // The moves of every position, checked against every other one, have to agree with the
+        // move generator
+        for (game, own) in games.iter().zip(&moves) {
+            for rev in moves.iter().flatten() {
+                assert_eq!(is_pseudo_legal(game, rev), own.contains(rev), "{:?}", rev);
+            }
+        }
+
+        // A wrong flag is enough to reject the move
+        let game = Game::read_fen(FEN2);
+        let push = moves[1].iter().find(|rev| rev.flag == Flag::Quiet).unwrap();
+        assert!(!is_pseudo_legal(&game, &PositionRev { flag: Flag::EP, ..*push }));
+        assert!(!is_pseudo_legal(
+            &game,
+            &PositionRev { flag: Flag::Capture(BLACK_QUEEN), ..*push }
+        ));
+    }
+
     // KING
 
     #[test]
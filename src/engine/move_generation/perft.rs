@@ -1,7 +1,8 @@
 use super::make_move::GameMoveTrait;
 use crate::engine::fen::fen::FenTrait;
 use crate::engine::game::Game;
-use crate::engine::move_generation::mv_gen::gen_moves;
+use crate::engine::move_generation::legal_mv_gen::gen_legal_moves;
+use crate::engine::move_generation::legal_mv_gen::legal_move_count;
 use crate::engine::shared::structures::internal_move::*;
 use std::fs::File;
 use std::time::Instant;
@@ -92,11 +93,9 @@ pub fn perft(depth: usize, game: &mut Game, stats: &mut Stats) -> u64 {
         return 1;
     }
 
-    let (irr, mut pos_rev) = gen_moves(game.color, game);
-    for rev in &mut pos_rev {
-        if !game.make_move(rev, &irr) {
-            continue;
-        }
+    let (irr, pos_rev) = gen_legal_moves(game);
+    for rev in &pos_rev {
+        game.make_legal_move(rev, &irr);
 
         if depth == 1 {
             match rev.flag {
@@ -115,6 +114,25 @@ pub fn perft(depth: usize, game: &mut Game, stats: &mut Stats) -> u64 {
     leaf_nodes
 }
 
+/// Perft without statistics, the leaves are bulk counted from the legal move count.
+pub fn perft_bulk(depth: usize, game: &mut Game) -> u64 {
+    match depth {
+        0 => return 1,
+        1 => return legal_move_count(game),
+        _ => (),
+    }
+
+    let mut leaf_nodes: u64 = 0;
+    let (irr, pos_rev) = gen_legal_moves(game);
+    for rev in &pos_rev {
+        game.make_legal_move(rev, &irr);
+        leaf_nodes += perft_bulk(depth - 1, game);
+        game.undo_move();
+    }
+
+    leaf_nodes
+}
+
 pub fn init_test_func(fen: &str, depth: usize, dispaly_stats: bool) -> Stats {
     let mut game = Game::read_fen(fen);
     let mut stats = Stats::init();
@@ -154,7 +172,86 @@ mod tests {
 
     use super::*;
 
+    // **** START: BULK COUNTING ****
+    #[test]
+    fn test_perft_bulk() {
+        let positions = [
+            (FEN_START, 5, 4865609),
+            (FEN_POS_TWO, 4, 4085603),
+            (FEN_POS_THREE, 6, 11030083),
+            (FEN_POS_FOUR, 5, 15833292),
+            (FEN_POS_FIVE, 4, 2103487),
+            (FEN_POS_SIX, 4, 3894594),
+            (FEN_BUG_2SQ_PAWN, 2, 44),
+        ];
+
+        for (fen, depth, nodes) in positions {
+            let mut game = Game::read_fen(fen);
+            assert_eq!(perft_bulk(depth, &mut game), nodes, "{}", fen);
+        }
+    }
+
     // **** START: OTHER POSITIONS ****
+    #[test]
+    fn test_perft_chess960() {
+        // Reference positions from the Chess960 perft suite (Shredder-FEN castling fields)
+        let positions = [
+            (
+                "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+                [21, 528, 12189, 326672],
+            ),
+            (
+                "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9",
+                [21, 807, 18002, 667366],
+            ),
+            (
+                "b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9",
+                [20, 479, 10471, 273318],
+            ),
+            (
+                "qbbnnrkr/2pp2pp/p7/1p2pp2/8/P3PP2/1PPP1KPP/QBBNNR1R w hf - 0 9",
+                [22, 593, 13440, 382958],
+            ),
+            (
+                "1nbbnrkr/p1p1ppp1/3p4/1p3P1p/3Pq2P/8/PPP1P1P1/QNBBNRKR w HFhf - 0 9",
+                [28, 1120, 31058, 1171749],
+            ),
+            (
+                "qnbnr1kr/ppp1b1pp/4p3/3p1p2/8/2NPP3/PPP1BPPP/QNB1R1KR w HEhe - 1 9",
+                [29, 899, 26578, 824055],
+            ),
+            (
+                "q1bnrkr1/ppppp2p/2n2p2/4b1p1/2NP4/8/PPP1PPPP/QNB1RRKB w ge - 1 9",
+                [30, 860, 24566, 732757],
+            ),
+            (
+                "qbn1brkr/ppp1p1p1/2n4p/3p1p2/P7/6PP/QPPPPP2/1BNNBRKR w HFhf - 0 9",
+                [25, 635, 17054, 465806],
+            ),
+            (
+                "qnnbbrkr/1p2ppp1/2pp3p/p7/1P5P/2NP4/P1P1PPP1/Q1NBBRKR w HFhf - 0 9",
+                [24, 572, 15243, 384260],
+            ),
+            (
+                "qn1rbbkr/ppp2p1p/1n1pp1p1/8/3P4/P6P/1PP1PPPK/QNNRBB1R w hd - 2 9",
+                [28, 811, 23175, 679699],
+            ),
+        ];
+
+        for (fen, nodes) in positions {
+            let mut game = Game::read_fen(fen);
+            for (depth, &expected) in nodes.iter().enumerate() {
+                assert_eq!(
+                    perft_bulk(depth + 1, &mut game),
+                    expected,
+                    "{} depth {}",
+                    fen,
+                    depth + 1
+                );
+            }
+        }
+    }
+
     #[test]
     fn test_fen_bug_2_sq_pawn_dept_1() {
         let stats = init_test_func(&FEN_BUG_2SQ_PAWN, 1, true);
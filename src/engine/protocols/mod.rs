@@ -1,2 +1,3 @@
+pub mod options;
 pub mod time;
 pub mod uci;
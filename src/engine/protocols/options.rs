@@ -0,0 +1,205 @@
+use crate::engine::search::transposition_table::{DEFAULT_HASH_MB, MAX_HASH_MB};
+
+pub const MAX_THREADS: usize = 256;
+pub const MAX_MULTI_PV: usize = 256;
+
+pub const HASH: &str = "Hash";
+pub const THREADS: &str = "Threads";
+pub const MULTI_PV: &str = "MultiPV";
+pub const MOVE_OVERHEAD: &str = "Move Overhead";
+pub const CLEAR_HASH: &str = "Clear Hash";
+pub const PONDER: &str = "Ponder";
+pub const CHESS960: &str = "UCI_Chess960";
+pub const SHOW_WDL: &str = "UCI_ShowWDL";
+pub const LIMIT_STRENGTH: &str = "UCI_LimitStrength";
+pub const ELO: &str = "UCI_Elo";
+
+// UCI_LimitStrength: the node budget at the lowest Elo, doubled every ELO_PER_DOUBLING points
+pub const MIN_ELO: i64 = 1350;
+pub const MAX_ELO: i64 = 2850;
+const MIN_ELO_NODES: usize = 1000;
+const ELO_PER_DOUBLING: usize = 100;
+
+// NOTE: UCI writes an empty string default as <empty>
+const EMPTY_STRING: &str = "<empty>";
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum OptionKind {
+    Spin { default: i64, min: i64, max: i64 },
+    Check { default: bool },
+    Button,
+    Str { default: &'static str },
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum OptionValue {
+    Spin(i64),
+    Check(bool),
+    Button,
+    Str(String),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct UciOption {
+    pub name: &'static str,
+    pub kind: OptionKind,
+}
+
+/// Every option the engine advertises after `uci`, in the order they are printed. There is no
+/// opening book, so OwnBook and BookFile are not offered.
+pub const OPTIONS: [UciOption; 10] = [
+    UciOption {
+        name: HASH,
+        kind: OptionKind::Spin { default: DEFAULT_HASH_MB as i64, min: 1, max: MAX_HASH_MB as i64 },
+    },
+    UciOption {
+        name: THREADS,
+        kind: OptionKind::Spin { default: 1, min: 1, max: MAX_THREADS as i64 },
+    },
+    UciOption {
+        name: MULTI_PV,
+        kind: OptionKind::Spin { default: 1, min: 1, max: MAX_MULTI_PV as i64 },
+    },
+    UciOption { name: MOVE_OVERHEAD, kind: OptionKind::Spin { default: 10, min: 0, max: 5000 } },
+    UciOption { name: CLEAR_HASH, kind: OptionKind::Button },
+    UciOption { name: PONDER, kind: OptionKind::Check { default: false } },
+    UciOption { name: CHESS960, kind: OptionKind::Check { default: false } },
+    UciOption { name: SHOW_WDL, kind: OptionKind::Check { default: false } },
+    UciOption { name: LIMIT_STRENGTH, kind: OptionKind::Check { default: false } },
+    UciOption {
+        name: ELO,
+        kind: OptionKind::Spin { default: MAX_ELO, min: MIN_ELO, max: MAX_ELO },
+    },
+];
+
+impl UciOption {
+    /// Option names are case insensitive in UCI.
+    pub fn find(name: &str) -> Option<&'static UciOption> {
+        OPTIONS.iter().find(|option| option.name.eq_ignore_ascii_case(name))
+    }
+
+    /// The `option name ...` line printed after `uci`.
+    pub fn to_uci(&self) -> String {
+        let kind = match self.kind {
+            OptionKind::Spin { default, min, max } => {
+                format!("spin default {} min {} max {}", default, min, max)
+            }
+            OptionKind::Check { default } => format!("check default {}", default),
+            OptionKind::Button => String::from("button"),
+            OptionKind::Str { default } => format!("string default {}", default),
+        };
+        format!("option name {} type {}", self.name, kind)
+    }
+
+    /// Checks the value given in `setoption` against the type and range of the option.
+    pub fn parse(&self, value: Option<&str>) -> Result<OptionValue, String> {
+        let value = value.map(str::trim).filter(|value| !value.is_empty());
+        match (self.kind, value) {
+            (OptionKind::Button, _) => Ok(OptionValue::Button),
+            (_, None) => Err(format!("Missing value for option {}", self.name)),
+            (OptionKind::Spin { min, max, .. }, Some(value)) => match value.parse::<i64>() {
+                Ok(v) if (min..=max).contains(&v) => Ok(OptionValue::Spin(v)),
+                Ok(v) => {
+                    Err(format!("{} value {} is out of range [{}, {}]", self.name, v, min, max))
+                }
+                Err(_) => Err(format!("Invalid {} value: {}", self.name, value)),
+            },
+            (OptionKind::Check { .. }, Some(value)) => match value.to_ascii_lowercase().as_str() {
+                "true" => Ok(OptionValue::Check(true)),
+                "false" => Ok(OptionValue::Check(false)),
+                _ => Err(format!("Invalid {} value: {}", self.name, value)),
+            },
+            (OptionKind::Str { .. }, Some(value)) => match value {
+                EMPTY_STRING => Ok(OptionValue::Str(String::new())),
+                _ => Ok(OptionValue::Str(value.to_string())),
+            },
+        }
+    }
+}
+
+/// The current values of the options that don't live anywhere else (the hash size is the
+/// size of the table itself).
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct EngineOptions {
+    pub threads: usize,
+    pub multi_pv: usize,
+    pub move_overhead: u64,
+    pub ponder: bool,
+    pub chess960: bool,
+    pub show_wdl: bool,
+    pub limit_strength: bool,
+    pub elo: usize,
+}
+
+impl EngineOptions {
+    pub fn init() -> Self {
+        Self {
+            threads: 1,
+            multi_pv: 1,
+            move_overhead: 10,
+            ponder: false,
+            chess960: false,
+            show_wdl: false,
+            limit_strength: false,
+            elo: MAX_ELO as usize,
+        }
+    }
+
+    /// The node budget of every search under UCI_LimitStrength, it doubles every 100 Elo.
+    pub fn strength_node_limit(&self) -> Option<usize> {
+        let doublings = self.elo.saturating_sub(MIN_ELO as usize) / ELO_PER_DOUBLING;
+        self.limit_strength.then(|| MIN_ELO_NODES << doublings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_option_lines() {
+        let line = |name| UciOption::find(name).unwrap().to_uci();
+        assert_eq!(line("Threads"), "option name Threads type spin default 1 min 1 max 256");
+        assert_eq!(line("clear hash"), "option name Clear Hash type button");
+        assert_eq!(line("UCI_Chess960"), "option name UCI_Chess960 type check default false");
+        assert_eq!(line("UCI_Elo"), "option name UCI_Elo type spin default 2850 min 1350 max 2850");
+        assert_eq!(UciOption::find("Contempt"), None);
+        assert_eq!(UciOption::find("OwnBook"), None);
+    }
+
+    #[test]
+    fn test_parse() {
+        let hash = UciOption::find(HASH).unwrap();
+        assert_eq!(hash.parse(Some("128")), Ok(OptionValue::Spin(128)));
+        assert!(hash.parse(Some("0")).is_err());
+        assert!(hash.parse(Some("lots")).is_err());
+        assert!(hash.parse(None).is_err());
+
+        let ponder = UciOption::find(PONDER).unwrap();
+        assert_eq!(ponder.parse(Some("True")), Ok(OptionValue::Check(true)));
+        assert!(ponder.parse(Some("yes")).is_err());
+
+        let book = UciOption { name: "BookFile", kind: OptionKind::Str { default: EMPTY_STRING } };
+        assert_eq!(
+            book.parse(Some("books/my book.bin")),
+            Ok(OptionValue::Str("books/my book.bin".into()))
+        );
+        assert_eq!(book.parse(Some("<empty>")), Ok(OptionValue::Str(String::new())));
+
+        assert_eq!(UciOption::find(CLEAR_HASH).unwrap().parse(None), Ok(OptionValue::Button));
+    }
+
+    #[test]
+    fn test_strength_node_limit() {
+        let mut options = EngineOptions::init();
+        assert_eq!(options.strength_node_limit(), None);
+
+        options.limit_strength = true;
+        options.elo = MIN_ELO as usize;
+        assert_eq!(options.strength_node_limit(), Some(MIN_ELO_NODES));
+        options.elo = 1550;
+        assert_eq!(options.strength_node_limit(), Some(MIN_ELO_NODES * 4));
+        options.elo = MAX_ELO as usize;
+        assert_eq!(options.strength_node_limit(), Some(MIN_ELO_NODES << 15));
+    }
+}
@@ -7,12 +7,16 @@ use std::{io, thread, u64};
 
 use crate::engine::fen::fen::FenTrait;
 use crate::engine::game::Game;
+use crate::engine::move_generation::legal_mv_gen::gen_legal_moves;
 use crate::engine::move_generation::make_move::GameMoveTrait;
-use crate::engine::search::searcher::iterative_deepening;
-use crate::engine::search::time::set_time_limit;
+use crate::engine::protocols::options::*;
+use crate::engine::search::bench::{bench, BenchConfig};
+use crate::engine::search::searcher::{iterative_deepening, lazy_smp, ponder_move};
+use crate::engine::search::time::TimeManager;
+use crate::engine::search::transposition_table::{TTTable, DEFAULT_HASH_MB};
 use crate::engine::shared::helper_func::const_utility::FEN_START;
 use crate::engine::shared::helper_func::print_utility::{
-    from_move_notation, move_notation, print_chess,
+    from_move_notation, print_chess, uci_move_notation,
 };
 use crate::engine::shared::structures::color::ColorTrait;
 use crate::engine::shared::structures::internal_move::{PositionIrr, PositionRev};
@@ -22,7 +26,13 @@ use crate::engine::{fen, game};
 pub struct UCI {
     pub game: Game,
     max_depth: usize,
-    is_searching: Arc<AtomicBool>,
+    options: EngineOptions,
+    // Kept across searches, only cleared on a new game or on Clear Hash
+    tt: Arc<TTTable>,
+    // Cancellation handle of the running search, set on stop and quit
+    stop: Arc<AtomicBool>,
+    // Set by go ponder, cleared by ponderhit
+    ponder: Arc<AtomicBool>,
     search_thread: Option<JoinHandle<()>>,
 }
 
@@ -31,7 +41,10 @@ impl UCI {
         UCI {
             game: Game::initialize(),
             max_depth: 64,
-            is_searching: Arc::new(AtomicBool::new(false)),
+            options: EngineOptions::init(),
+            tt: Arc::new(TTTable::init(DEFAULT_HASH_MB)),
+            stop: Arc::new(AtomicBool::new(false)),
+            ponder: Arc::new(AtomicBool::new(false)),
             search_thread: None,
         }
     }
@@ -79,10 +92,13 @@ impl UCI {
                             break;
                         }
                         "stop" => self.stop(),
+                        "ponderhit" => self.ponderhit(),
                         "isready" => self.isready(),
+                        "setoption" => self.setoption(&args[1..]),
                         "ucinewgame" => self.ucinewgame(),
                         "position" => self.position(&args[1..]),
                         "go" => self.go(&args[1..]),
+                        "bench" => self.bench(&args[1..]),
                         _ => eprintln!("info string Unknown command: {}", args[0]),
                     }
                 }
@@ -101,13 +117,65 @@ impl UCI {
     fn uci(&mut self) {
         println!("id name {}", "Challenger 1.0");
         println!("id author Nikola Simjanovski");
+        for option in &OPTIONS {
+            println!("{}", option.to_uci());
+        }
         println!("uciok");
     }
 
+    fn setoption(&mut self, args: &[&str]) {
+        // Names (Clear Hash) and values (BookFile) can have spaces, the name runs until "value"
+        let name = args
+            .iter()
+            .skip_while(|&&arg| arg != "name")
+            .skip(1)
+            .take_while(|&&arg| arg != "value")
+            .copied()
+            .collect::<Vec<_>>()
+            .join(" ");
+        let value = args.iter().position(|&arg| arg == "value").map(|i| args[i + 1..].join(" "));
+
+        let Some(option) = UciOption::find(&name) else {
+            eprintln!("info string Unknown option: {}", name);
+            return;
+        };
+        let value = match option.parse(value.as_deref()) {
+            Ok(value) => value,
+            Err(e) => {
+                eprintln!("info string {}", e);
+                return;
+            }
+        };
+
+        self.abort_search();
+        let opts = &mut self.options;
+        match (option.name, value) {
+            (HASH, OptionValue::Spin(mb)) => self.tt = Arc::new(TTTable::init(mb as usize)),
+            (THREADS, OptionValue::Spin(threads)) => opts.threads = threads as usize,
+            (MULTI_PV, OptionValue::Spin(multi_pv)) => opts.multi_pv = multi_pv as usize,
+            (MOVE_OVERHEAD, OptionValue::Spin(ms)) => opts.move_overhead = ms as u64,
+            (CLEAR_HASH, _) => self.tt.clear(),
+            (PONDER, OptionValue::Check(ponder)) => opts.ponder = ponder,
+            (CHESS960, OptionValue::Check(chess960)) => {
+                opts.chess960 = chess960;
+                self.game.chess960 = chess960;
+            }
+            (SHOW_WDL, OptionValue::Check(show_wdl)) => opts.show_wdl = show_wdl,
+            (LIMIT_STRENGTH, OptionValue::Check(limit)) => opts.limit_strength = limit,
+            (ELO, OptionValue::Spin(elo)) => opts.elo = elo as usize,
+            (name, value) => eprintln!("info string Option {} ignored value {:?}", name, value),
+        }
+    }
+
     fn stop(&mut self) {
         self.stop_search();
     }
 
+    /// The expected move was played, the search goes on with the clock of the go command.
+    fn ponderhit(&mut self) {
+        self.ponder.store(false, Ordering::Relaxed);
+    }
+
     fn isready(&mut self) {
         println!("readyok");
     }
@@ -117,6 +185,13 @@ impl UCI {
 
         self.max_depth = 64;
         self.game.reset_board();
+        self.tt.clear();
+    }
+
+    /// Runs synchronously with its own table, the table of the game is left alone.
+    fn bench(&mut self, args: &[&str]) {
+        self.abort_search();
+        bench(BenchConfig::parse(args));
     }
 
     fn position(&mut self, args: &[&str]) {
@@ -141,6 +216,7 @@ impl UCI {
 
         self.game.info.moves_played = 0;
         self.game = Game::read_fen(&fen.join(" "));
+        self.game.chess960 = self.options.chess960;
 
         for s in moves {
             let (irr, rev) = from_move_notation(s, &self.game);
@@ -155,26 +231,42 @@ impl UCI {
 
         let mut depth: Option<u8> = None;
         let mut infinite = false;
-        let mut time_limit: Option<Duration> = None;
-
-        let mut wtime: Option<usize> = None;
-        let mut btime: Option<usize> = None;
-        let mut winc: Option<usize> = None;
-        let mut binc: Option<usize> = None;
-        let mut moves_togo: Option<usize> = None;
-
-        let mut iter = args.iter();
+        let mut movetime: Option<u64> = None;
+
+        // NOTE: Some GUIs send a negative time when the clock ran out
+        let ms = |v: Option<&&str>| v.and_then(|v| v.parse::<i64>().ok()).map(|v| v.max(0) as u64);
+        let mut wtime: Option<u64> = None;
+        let mut btime: Option<u64> = None;
+        let mut winc: Option<u64> = None;
+        let mut binc: Option<u64> = None;
+        let mut moves_togo: Option<u64> = None;
+
+        let mut node_limit: Option<usize> = None;
+        let mut mate: Option<usize> = None;
+        let mut search_moves: Vec<PositionRev> = Vec::new();
+        let mut ponder = false;
+        let (_, legal) = gen_legal_moves(&self.game);
+
+        let mut iter = args.iter().peekable();
         while let Some(arg) = iter.next() {
             match *arg {
-                "searchmoves" => (), // TODO:
-                "ponder" => (),      // TODO:
-                "nodes" => (),       // TODO:
-                "mate" => (),        // TODO:
-                "wtime" => wtime = iter.next().and_then(|v| v.parse().ok()),
-                "btime" => btime = iter.next().and_then(|v| v.parse().ok()),
-                "winc" => winc = iter.next().and_then(|v| v.parse().ok()),
-                "binc" => binc = iter.next().and_then(|v| v.parse().ok()),
-                "movestogo" => moves_togo = iter.next().and_then(|v| v.parse().ok()),
+                // Takes every following token that is a legal move
+                "searchmoves" => {
+                    while let Some(rev) = iter.peek().and_then(|&&notation| {
+                        legal.iter().find(|rev| uci_move_notation(rev, &self.game) == notation)
+                    }) {
+                        search_moves.push(*rev);
+                        iter.next();
+                    }
+                }
+                "ponder" => ponder = true,
+                "nodes" => node_limit = iter.next().and_then(|v| v.parse().ok()),
+                "mate" => mate = iter.next().and_then(|v| v.parse().ok()),
+                "wtime" => wtime = ms(iter.next()),
+                "btime" => btime = ms(iter.next()),
+                "winc" => winc = ms(iter.next()),
+                "binc" => binc = ms(iter.next()),
+                "movestogo" => moves_togo = ms(iter.next()),
                 "depth" => {
                     if let Some(d) = iter.next().and_then(|v| v.parse().ok()) {
                         depth = Some(d);
@@ -182,8 +274,8 @@ impl UCI {
                     }
                 }
                 "movetime" => {
-                    if let Some(time) = iter.next().and_then(|v| v.parse::<u64>().ok()) {
-                        time_limit = Some(Duration::from_millis(time));
+                    if let Some(time) = ms(iter.next()) {
+                        movetime = Some(time);
                         infinite = false;
                     }
                 }
@@ -192,10 +284,38 @@ impl UCI {
             }
         }
 
-        self.is_searching.store(false, Ordering::Relaxed);
+        self.stop.store(false, Ordering::Relaxed);
+        self.ponder.store(ponder, Ordering::Relaxed);
 
         let mut game_clone = self.game.clone();
-        let stop_flag_clone = Arc::clone(&self.is_searching);
+        let threads = self.options.threads;
+        game_clone.info.multi_pv = self.options.multi_pv;
+        game_clone.info.show_wdl = self.options.show_wdl;
+        // The tighter of go nodes and the strength limit
+        game_clone.info.node_limit = match (node_limit, self.options.strength_node_limit()) {
+            (Some(nodes), Some(limit)) => Some(nodes.min(limit)),
+            (nodes, limit) => nodes.or(limit),
+        };
+        game_clone.info.mate = mate;
+        game_clone.info.search_moves = search_moves;
+
+        // Without a clock (go nodes, go mate, go depth) only the other limits apply
+        let overhead = self.options.move_overhead;
+        let (time, inc) = match self.game.color.is_white() {
+            true => (wtime, winc),
+            false => (btime, binc),
+        };
+        game_clone.info.time = match (infinite, movetime, time) {
+            (true, _, _) => None,
+            (false, Some(movetime), _) => Some(TimeManager::fixed(movetime, overhead)),
+            (false, None, Some(time)) => {
+                Some(TimeManager::init(time, inc.unwrap_or(0), moves_togo, overhead))
+            }
+            (false, None, None) => None,
+        };
+        let tt = Arc::clone(&self.tt);
+        let stop = Arc::clone(&self.stop);
+        let ponder = Arc::clone(&self.ponder);
 
         let handle = thread::spawn(move || {
             game_clone.info.start_time = Instant::now();
@@ -203,41 +323,27 @@ impl UCI {
             game_clone.info.depth = depth.or(Some(30));
             game_clone.info.infinite = infinite;
 
-            if !infinite && matches!(time_limit, None) && game_clone.color.is_white() {
-                game_clone.info.time_limit = Some(set_time_limit(
-                    game_clone.info.moves_played,
-                    wtime.unwrap_or(0),
-                    winc.unwrap_or(0),
-                ));
-            } else if !infinite && matches!(time_limit, None) && game_clone.color.is_black() {
-                game_clone.info.time_limit = Some(set_time_limit(
-                    game_clone.info.moves_played,
-                    btime.unwrap_or(0),
-                    binc.unwrap_or(0),
-                ));
-            } else {
-                game_clone.info.time_limit = time_limit.or(Some(Duration::from_millis(u64::MAX)));
+            let best_move: Option<PositionRev> =
+                lazy_smp(&mut game_clone, threads, &tt, &stop, &ponder);
+
+            // In infinite mode and while pondering the bestmove is only sent once the GUI asks
+            while (infinite || ponder.load(Ordering::Relaxed)) && !stop.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_millis(1));
             }
 
-            let best_move: Option<PositionRev> = iterative_deepening(&mut game_clone);
-
-            if !stop_flag_clone.load(Ordering::Relaxed) || best_move.is_some() {
-                if let Some(mv) = best_move {
-                    println!(
-                        "bestmove {}",
-                        move_notation(mv.from, mv.to, mv.flag.get_promo_piece())
-                    );
-                } else {
-                    if !stop_flag_clone.load(Ordering::Relaxed) {
-                        eprintln!(
-                            "info string Search finished but no move found (e.g., game over)."
-                        );
-                    } else {
-                        eprintln!("info string Search stopped before finding a best move.");
-                    }
+            match best_move {
+                Some(mv) => {
+                    let ponder = match ponder_move(&mut game_clone, &mv) {
+                        Some(reply) => {
+                            format!(" ponder {}", uci_move_notation(&reply, &game_clone))
+                        }
+                        None => String::new(),
+                    };
+                    println!("bestmove {}{}", uci_move_notation(&mv, &game_clone), ponder);
+                }
+                None => {
+                    eprintln!("info string Search finished but no move found (e.g., game over).")
                 }
-            } else {
-                eprintln!("info string Search stopped externally before finding a best move.");
             }
         });
 
@@ -246,15 +352,13 @@ impl UCI {
 
     fn start_search(&mut self) {
         self.game.info.stopped = false;
-        let mv = iterative_deepening(&mut self.game);
+        let mv = iterative_deepening(&mut self.game, &self.tt);
         println!("{:?}", mv);
     }
 
     fn stop_search(&mut self) {
-        self.game.info.stopped = true;
-
         if self.search_thread.is_some() {
-            self.is_searching.store(true, Ordering::Relaxed);
+            self.stop.store(true, Ordering::Relaxed);
         }
     }
 
@@ -0,0 +1,219 @@
+use crate::engine::{
+    game::Game,
+    shared::structures::{
+        internal_move::{Flag, PositionRev},
+        piece::{Piece, PieceTrait, PAWN},
+    },
+};
+
+// Every table entry stays within [-MAX_HISTORY, MAX_HISTORY]
+pub const MAX_HISTORY: i32 = 16384;
+const MAX_BONUS: i32 = 1600;
+
+const PIECES: usize = 14;
+const SQUARES: usize = 64;
+const PIECE_TYPES: usize = 6;
+const CONTINUATION_SIZE: usize = PIECES * SQUARES * PIECES * SQUARES;
+
+/// The history heuristics used to order the moves that the hash move and the killers don't
+/// cover. All tables are updated with gravity: an entry moves towards the bonus by an amount
+/// that shrinks the closer it already is to the bound, so the values never saturate and recent
+/// results weigh more than old ones.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct History {
+    /// Quiet moves by moved piece and target square (butterfly history).
+    quiet: [[i16; SQUARES]; PIECES],
+    /// Captures by moved piece, target square and captured piece type.
+    capture: [[[i16; PIECE_TYPES]; SQUARES]; PIECES],
+    /// The quiet move that refuted a move last time, by moved piece and target square of it.
+    counter: [[Option<PositionRev>; SQUARES]; PIECES],
+    /// Quiet moves by the move played one and two plies before them (piece and target square
+    /// of both moves). Flat and boxed, the table is too big for the stack.
+    continuation: Box<[i16]>,
+}
+
+impl History {
+    pub fn init() -> Self {
+        Self {
+            quiet: [[0; SQUARES]; PIECES],
+            capture: [[[0; PIECE_TYPES]; SQUARES]; PIECES],
+            counter: [[None; SQUARES]; PIECES],
+            continuation: vec![0; CONTINUATION_SIZE].into_boxed_slice(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.quiet.iter_mut().for_each(|arr| arr.fill(0));
+        self.capture.iter_mut().flatten().for_each(|arr| arr.fill(0));
+        self.counter.iter_mut().for_each(|arr| arr.fill(None));
+        self.continuation.fill(0);
+    }
+
+    /// Butterfly plus the continuation history of the last two moves (`prev`, the latest first).
+    pub fn quiet_score(&self, rev: &PositionRev, prev: &[Option<PositionRev>; 2]) -> i32 {
+        let mut score = self.quiet[rev.piece.idx()][rev.to as usize] as i32;
+        for p in prev.iter().flatten() {
+            score += self.continuation[cont_idx(p, rev)] as i32;
+        }
+        score
+    }
+
+    pub fn capture_score(&self, rev: &PositionRev) -> i32 {
+        match captured(rev) {
+            Some(cap) => self.capture[rev.piece.idx()][rev.to as usize][type_idx(cap)] as i32,
+            None => 0,
+        }
+    }
+
+    pub fn counter_move(&self, prev: Option<PositionRev>) -> Option<PositionRev> {
+        prev.and_then(|p| self.counter[p.piece.idx()][p.to as usize])
+    }
+
+    /// A quiet move caused a cutoff: it gets a bonus, the quiet moves searched before it a
+    /// malus, and it becomes the counter move of the previous move.
+    pub fn update_quiets(
+        &mut self,
+        best: &PositionRev,
+        tried: &[PositionRev],
+        prev: &[Option<PositionRev>; 2],
+        depth: u8,
+    ) {
+        let bonus = stat_bonus(depth);
+        self.update_quiet(best, prev, bonus);
+        for rev in tried.iter().filter(|rev| *rev != best) {
+            self.update_quiet(rev, prev, -bonus);
+        }
+
+        if let Some(p) = prev[0] {
+            self.counter[p.piece.idx()][p.to as usize] = Some(*best);
+        }
+    }
+
+    /// A cutoff of any move punishes the captures that were searched before it and failed to
+    /// cut, a capture that cut gets the bonus.
+    pub fn update_captures(
+        &mut self,
+        best: Option<&PositionRev>,
+        tried: &[PositionRev],
+        depth: u8,
+    ) {
+        let bonus = stat_bonus(depth);
+        if let Some(best) = best {
+            self.update_capture(best, bonus);
+        }
+        for rev in tried.iter().filter(|rev| Some(*rev) != best) {
+            self.update_capture(rev, -bonus);
+        }
+    }
+
+    fn update_quiet(&mut self, rev: &PositionRev, prev: &[Option<PositionRev>; 2], bonus: i32) {
+        gravity(&mut self.quiet[rev.piece.idx()][rev.to as usize], bonus);
+        for p in prev.iter().flatten() {
+            gravity(&mut self.continuation[cont_idx(p, rev)], bonus);
+        }
+    }
+
+    fn update_capture(&mut self, rev: &PositionRev, bonus: i32) {
+        if let Some(cap) = captured(rev) {
+            gravity(&mut self.capture[rev.piece.idx()][rev.to as usize][type_idx(cap)], bonus);
+        }
+    }
+}
+
+/// The moves played one and two plies before the current node of the search.
+#[inline(always)]
+pub fn prev_moves(game: &Game) -> [Option<PositionRev>; 2] {
+    let prev = |n: usize| game.ply.checked_sub(n).and_then(|ply| game.s_moves[ply]);
+    [prev(1), prev(2)]
+}
+
+#[inline(always)]
+fn stat_bonus(depth: u8) -> i32 {
+    let depth = depth as i32;
+    (depth * depth * 16 + depth * 32).min(MAX_BONUS)
+}
+
+#[inline(always)]
+fn gravity(entry: &mut i16, bonus: i32) {
+    let value = *entry as i32;
+    *entry = (value + bonus - value * bonus.abs() / MAX_HISTORY) as i16;
+}
+
+#[inline(always)]
+fn cont_idx(prev: &PositionRev, rev: &PositionRev) -> usize {
+    ((prev.piece.idx() * SQUARES + prev.to as usize) * PIECES + rev.piece.idx()) * SQUARES
+        + rev.to as usize
+}
+
+#[inline(always)]
+fn captured(rev: &PositionRev) -> Option<Piece> {
+    match rev.flag {
+        Flag::Capture(cap) | Flag::Promotion(_, Some(cap)) => Some(cap),
+        Flag::EP => Some(PAWN),
+        _ => None,
+    }
+}
+
+#[inline(always)]
+fn type_idx(piece: Piece) -> usize {
+    (piece.kind() / 2).idx() - 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::shared::structures::{
+        piece::{BLACK_KNIGHT, WHITE_BISHOP, WHITE_KNIGHT},
+        square::SqPos::*,
+    };
+
+    fn quiet(piece: Piece, from: usize, to: usize) -> PositionRev {
+        PositionRev::init(from as u8, to as u8, piece, Flag::Quiet)
+    }
+
+    #[test]
+    fn test_gravity_is_bounded() {
+        let mut entry = 0;
+        for _ in 0..1000 {
+            gravity(&mut entry, MAX_BONUS);
+        }
+        assert!(entry as i32 <= MAX_HISTORY && entry > 0);
+
+        for _ in 0..1000 {
+            gravity(&mut entry, -MAX_BONUS);
+        }
+        assert!(entry as i32 >= -MAX_HISTORY && entry < 0);
+    }
+
+    #[test]
+    fn test_update_quiets() {
+        let mut history = History::init();
+        let prev = [Some(quiet(BLACK_KNIGHT, G8 as usize, F6 as usize)), None];
+        let best = quiet(WHITE_KNIGHT, G1 as usize, F3 as usize);
+        let other = quiet(WHITE_BISHOP, F1 as usize, C4 as usize);
+
+        history.update_quiets(&best, &[other, best], &prev, 6);
+        assert!(history.quiet_score(&best, &prev) > history.quiet_score(&best, &[None, None]));
+        assert!(history.quiet_score(&other, &prev) < 0);
+        assert_eq!(history.counter_move(prev[0]), Some(best));
+        assert_eq!(history.counter_move(None), None);
+
+        history.clear();
+        assert_eq!(history.quiet_score(&best, &prev), 0);
+        assert_eq!(history.counter_move(prev[0]), None);
+    }
+
+    #[test]
+    fn test_update_captures() {
+        let mut history = History::init();
+        let take = |piece, cap| PositionRev::init(G1 as u8, F3 as u8, piece, Flag::Capture(cap));
+        let (best, other) = (take(WHITE_KNIGHT, BLACK_KNIGHT), take(WHITE_BISHOP, BLACK_KNIGHT));
+
+        history.update_captures(Some(&best), &[other, best], 4);
+        assert!(history.capture_score(&best) > 0);
+        assert!(history.capture_score(&other) < 0);
+
+        history.update_captures(None, &[best], 4);
+        assert!(history.capture_score(&best) < stat_bonus(4));
+    }
+}
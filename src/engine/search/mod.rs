@@ -1,3 +1,8 @@
+pub mod bench;
+pub mod history;
+pub mod move_picker;
+pub mod pv_table;
+pub mod score;
 pub mod searcher;
 pub mod time;
 pub mod transposition_table;
@@ -0,0 +1,302 @@
+use crate::engine::{
+    evaluation::see::see_ge,
+    game::Game,
+    move_generation::{
+        legal_mv_gen::{add_legal_moves, is_legal, GenType, LegalMasks},
+        mv_gen::is_pseudo_legal,
+    },
+    search::history::prev_moves,
+    shared::structures::{
+        internal_move::{Flag, PositionRev},
+        piece::{PieceTrait, PAWN},
+    },
+};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, PartialOrd, Ord)]
+pub enum Stage {
+    TTMove,
+    GenCaptures,
+    GoodCaptures,
+    Killers,
+    CounterMove,
+    GenQuiets,
+    Quiets,
+    BadCaptures,
+    Done,
+}
+
+/// Hands out the legal moves of a position one at a time, best first. Each group of moves is only
+/// generated once the previous stage is exhausted, so a cutoff on the hash move or on a good
+/// capture never pays for the quiet move generation.
+pub struct MovePicker {
+    pub stage: Stage,
+    pub masks: LegalMasks,
+    captures_only: bool,
+    quiet_checks: bool,
+
+    tt_mv: Option<PositionRev>,
+    killers: [Option<PositionRev>; 2],
+    killer_idx: usize,
+    counter: Option<PositionRev>,
+    prev: [Option<PositionRev>; 2],
+
+    moves: Vec<(PositionRev, isize)>,
+    bad_captures: Vec<(PositionRev, isize)>,
+}
+
+impl MovePicker {
+    pub fn init(game: &Game, tt_mv: Option<PositionRev>) -> Self {
+        let masks = LegalMasks::init(game);
+        let prev = prev_moves(game);
+        Self {
+            stage: Stage::TTMove,
+            masks,
+            captures_only: false,
+            quiet_checks: false,
+            tt_mv: tt_mv.filter(|mv| is_playable(game, &masks, mv)),
+            killers: game.s_killers[game.ply],
+            killer_idx: 0,
+            counter: game.s_history.counter_move(prev[0]),
+            prev,
+            moves: Vec::with_capacity(64),
+            bad_captures: Vec::with_capacity(16),
+        }
+    }
+
+    /// Picker used by the quiescence search, it only hands out captures that don't lose material.
+    /// With `quiet_checks` they are followed by the quiet checks that don't lose material either.
+    pub fn init_captures(game: &Game, tt_mv: Option<PositionRev>, quiet_checks: bool) -> Self {
+        let masks = LegalMasks::init(game);
+        Self {
+            stage: Stage::TTMove,
+            masks,
+            captures_only: true,
+            quiet_checks,
+            tt_mv: tt_mv.filter(|mv| mv.flag.is_capture() && is_playable(game, &masks, mv)),
+            killers: [None; 2],
+            killer_idx: 0,
+            counter: None,
+            prev: prev_moves(game),
+            moves: Vec::with_capacity(32),
+            bad_captures: Vec::with_capacity(16),
+        }
+    }
+
+    pub fn next(&mut self, game: &Game) -> Option<PositionRev> {
+        loop {
+            match self.stage {
+                Stage::TTMove => {
+                    self.stage = Stage::GenCaptures;
+                    if self.tt_mv.is_some() {
+                        return self.tt_mv;
+                    }
+                }
+                Stage::GenCaptures => {
+                    let mut captures = Vec::with_capacity(32);
+                    add_legal_moves(game, &self.masks, GenType::Captures, &mut captures);
+
+                    for rev in captures {
+                        if Some(rev) == self.tt_mv {
+                            continue;
+                        }
+
+                        let score = capture_score(game, &rev);
+                        match is_bad_capture(game, &rev) {
+                            true => self.bad_captures.push((rev, score)),
+                            false => self.moves.push((rev, score)),
+                        }
+                    }
+                    self.stage = Stage::GoodCaptures;
+                }
+                Stage::GoodCaptures => match pick_best(&mut self.moves) {
+                    Some(rev) => return Some(rev),
+                    // The quiescence search never plays losing captures
+                    None => {
+                        self.stage = match (self.captures_only, self.quiet_checks) {
+                            (true, true) => Stage::GenQuiets,
+                            (true, false) => Stage::Done,
+                            (false, _) => Stage::Killers,
+                        }
+                    }
+                },
+                Stage::Killers => {
+                    while self.killer_idx < self.killers.len() {
+                        let killer = self.killers[self.killer_idx];
+                        self.killer_idx += 1;
+
+                        if let Some(rev) = killer {
+                            if Some(rev) != self.tt_mv
+                                && !rev.flag.is_capture()
+                                && is_playable(game, &self.masks, &rev)
+                            {
+                                return Some(rev);
+                            }
+                        }
+                    }
+                    self.stage = Stage::CounterMove;
+                }
+                Stage::CounterMove => {
+                    self.stage = Stage::GenQuiets;
+                    // Only kept when it is handed out here, so the quiets don't skip it
+                    self.counter = self.counter.filter(|rev| {
+                        Some(*rev) != self.tt_mv
+                            && !self.killers.contains(&Some(*rev))
+                            && rev.flag.is_quiet()
+                            && is_playable(game, &self.masks, rev)
+                    });
+                    if self.counter.is_some() {
+                        return self.counter;
+                    }
+                }
+                Stage::GenQuiets => {
+                    let gen_type = match self.captures_only {
+                        true => GenType::QuietChecks,
+                        false => GenType::Quiets,
+                    };
+                    let mut quiets = Vec::with_capacity(64);
+                    add_legal_moves(game, &self.masks, gen_type, &mut quiets);
+
+                    for rev in quiets {
+                        if Some(rev) == self.tt_mv
+                            || self.killers.contains(&Some(rev))
+                            || Some(rev) == self.counter
+                        {
+                            continue;
+                        }
+                        if self.captures_only && !see_ge(game, &rev, 0) {
+                            continue;
+                        }
+                        self.moves.push((rev, quiet_score(game, &rev, &self.prev)));
+                    }
+                    self.stage = Stage::Quiets;
+                }
+                Stage::Quiets => match pick_best(&mut self.moves) {
+                    Some(rev) => return Some(rev),
+                    None => {
+                        self.stage = match self.captures_only {
+                            true => Stage::Done,
+                            false => Stage::BadCaptures,
+                        }
+                    }
+                },
+                Stage::BadCaptures => match pick_best(&mut self.bad_captures) {
+                    Some(rev) => return Some(rev),
+                    None => self.stage = Stage::Done,
+                },
+                Stage::Done => return None,
+            }
+        }
+    }
+}
+
+/// Moves that don't come from the generator (TT move, killers) have to be verified first.
+#[inline(always)]
+fn is_playable(game: &Game, masks: &LegalMasks, rev: &PositionRev) -> bool {
+    is_pseudo_legal(game, rev) && is_legal(game, masks, rev)
+}
+
+/// Removes and returns the highest scored move (selection sort, one step at a time).
+#[inline(always)]
+fn pick_best(moves: &mut Vec<(PositionRev, isize)>) -> Option<PositionRev> {
+    let best_idx = moves.iter().enumerate().max_by_key(|(_, (_, score))| *score)?.0;
+    Some(moves.swap_remove(best_idx).0)
+}
+
+/// Most Valuable Victim - Least Valuable Aggressor.
+#[inline(always)]
+pub fn mvv_lva(rev: &PositionRev) -> isize {
+    match rev.flag {
+        Flag::Capture(cap) => cap.weight() * 100 - rev.piece.weight(),
+        Flag::EP => PAWN.weight() * 100 - rev.piece.weight(),
+        Flag::Promotion(promo, Some(cap)) => (cap.weight() + promo.weight()) * 100,
+        Flag::Promotion(promo, None) => promo.weight() * 100,
+        _ => 0,
+    }
+}
+
+/// A capture is losing when the exchange it starts loses material. Taking a piece that is worth
+/// at least as much as the attacker can't lose, so SEE is skipped for those.
+#[inline(always)]
+fn is_bad_capture(game: &Game, rev: &PositionRev) -> bool {
+    match rev.flag {
+        Flag::Capture(cap) if cap.weight() >= rev.piece.weight() => false,
+        _ => !see_ge(game, rev, 0),
+    }
+}
+
+/// MVV-LVA, the capture history only decides between captures of similar value.
+#[inline(always)]
+fn capture_score(game: &Game, rev: &PositionRev) -> isize {
+    mvv_lva(rev) + (game.s_history.capture_score(rev) / CAPTURE_HISTORY_DIV) as isize
+}
+
+#[inline(always)]
+fn quiet_score(game: &Game, rev: &PositionRev, prev: &[Option<PositionRev>; 2]) -> isize {
+    match rev.flag {
+        Flag::Promotion(promo, None) => QUIET_PROMO_SCORE + promo.weight(),
+        _ => game.s_history.quiet_score(rev, prev) as isize,
+    }
+}
+
+const QUIET_PROMO_SCORE: isize = 1 << 40;
+const CAPTURE_HISTORY_DIV: i32 = 8;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{
+        fen::fen::FenTrait,
+        move_generation::legal_mv_gen::gen_legal_moves,
+        shared::{
+            helper_func::const_utility::{FEN_POS_FIVE, FEN_POS_TWO, FEN_START},
+            structures::{piece::WHITE_PAWN, square::SqPos::*},
+        },
+    };
+
+    fn picked_moves(game: &Game, tt_mv: Option<PositionRev>) -> Vec<PositionRev> {
+        let mut picker = MovePicker::init(game, tt_mv);
+        let mut moves = Vec::new();
+        while let Some(rev) = picker.next(game) {
+            moves.push(rev);
+        }
+        moves
+    }
+
+    #[test]
+    fn test_picker_yields_every_move_once() {
+        for fen in [FEN_START, FEN_POS_TWO, FEN_POS_FIVE] {
+            let game = Game::read_fen(fen);
+            let (_, mut expected) = gen_legal_moves(&game);
+            let tt_mv = expected.last().copied();
+            let mut moves = picked_moves(&game, tt_mv);
+
+            assert_eq!(moves[0], tt_mv.unwrap());
+            let key = |rev: &PositionRev| (rev.from, rev.to, rev.flag.get_promo_piece());
+            expected.sort_by_key(key);
+            moves.sort_by_key(key);
+            assert_eq!(expected, moves);
+        }
+    }
+
+    #[test]
+    fn test_picker_rejects_illegal_tt_move() {
+        let game = Game::read_fen(FEN_START);
+        let tt_mv = PositionRev::init(E2 as u8, E5 as u8, WHITE_PAWN, Flag::Quiet);
+        let moves = picked_moves(&game, Some(tt_mv));
+
+        assert_eq!(moves.len(), 20);
+        assert!(!moves.contains(&tt_mv));
+    }
+
+    #[test]
+    fn test_picker_captures_first() {
+        let game = Game::read_fen(FEN_POS_TWO);
+        let moves = picked_moves(&game, None);
+        let captures = moves.iter().take_while(|rev| rev.flag.is_capture()).count();
+
+        assert!(captures > 0);
+        assert!(moves[captures..]
+            .iter()
+            .all(|rev| !rev.flag.is_capture() || is_bad_capture(&game, rev)));
+    }
+}
@@ -0,0 +1,90 @@
+use crate::engine::{
+    search::searcher::MAX_PLY,
+    shared::structures::internal_move::{Flag, PositionRev},
+};
+
+const NO_MOVE: PositionRev = PositionRev { from: 0, to: 0, piece: 0, flag: Flag::Quiet };
+
+/// Triangular PV table: row `ply` holds the principal variation from that ply on. A node
+/// empties its row on entry and, when a move raises alpha, rebuilds it from the move and the
+/// row of the child, so the lines are tracked without any allocation.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PvTable {
+    moves: [[PositionRev; MAX_PLY]; MAX_PLY],
+    len: [usize; MAX_PLY],
+}
+
+impl PvTable {
+    pub fn init() -> Self {
+        Self { moves: [[NO_MOVE; MAX_PLY]; MAX_PLY], len: [0; MAX_PLY] }
+    }
+
+    #[inline(always)]
+    pub fn clear(&mut self, ply: usize) {
+        self.len[ply] = 0;
+    }
+
+    /// `rev` followed by the line of the child node.
+    #[inline(always)]
+    pub fn update(&mut self, ply: usize, rev: PositionRev) {
+        let child_len = match ply + 1 < MAX_PLY {
+            true => self.len[ply + 1].min(MAX_PLY - ply - 1),
+            false => 0,
+        };
+
+        let (row, rest) = self.moves.split_at_mut(ply + 1);
+        row[ply][0] = rev;
+        if child_len > 0 {
+            row[ply][1..=child_len].copy_from_slice(&rest[0][..child_len]);
+        }
+        self.len[ply] = child_len + 1;
+    }
+
+    #[inline(always)]
+    pub fn line(&self, ply: usize) -> &[PositionRev] {
+        &self.moves[ply][..self.len[ply]]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::shared::structures::piece::{BLACK_PAWN, WHITE_PAWN};
+
+    fn mv(piece: u8, from: u8, to: u8) -> PositionRev {
+        PositionRev::init(from, to, piece, Flag::Quiet)
+    }
+
+    #[test]
+    fn test_update_builds_the_line() {
+        let mut pv = PvTable::init();
+        let (e4, e5, d4) = (mv(WHITE_PAWN, 12, 28), mv(BLACK_PAWN, 52, 36), mv(WHITE_PAWN, 11, 27));
+
+        pv.clear(2);
+        pv.update(2, d4);
+        pv.clear(1);
+        pv.update(1, e5);
+        pv.clear(0);
+        pv.update(0, e4);
+        assert_eq!(pv.line(0), &[e4, e5, d4]);
+
+        // A better move at ply 1 whose child has no line cuts it short
+        pv.clear(2);
+        pv.update(1, d4);
+        pv.update(0, e4);
+        assert_eq!(pv.line(0), &[e4, d4]);
+
+        pv.clear(0);
+        assert!(pv.line(0).is_empty());
+    }
+
+    #[test]
+    fn test_last_ply() {
+        let mut pv = PvTable::init();
+        pv.update(MAX_PLY - 1, mv(WHITE_PAWN, 12, 28));
+        assert_eq!(pv.line(MAX_PLY - 1).len(), 1);
+
+        pv.update(MAX_PLY - 2, mv(BLACK_PAWN, 52, 36));
+        assert_eq!(pv.line(MAX_PLY - 2).len(), 2);
+    }
+}
@@ -0,0 +1,134 @@
+/// Search score in centipawns, from the point of view of the side to move. Scores beyond
+/// `MATE_BOUND` encode a forced mate: `MATE - score` is the distance to it in plies.
+pub type Score = isize;
+
+pub const INF: Score = 32000;
+pub const MATE: Score = 31000;
+pub const MATE_BOUND: Score = MATE - 256;
+
+/// Score of delivering mate `ply` plies from the root.
+#[inline(always)]
+pub fn mate_in(ply: usize) -> Score {
+    MATE - ply as Score
+}
+
+/// Score of getting mated `ply` plies from the root.
+#[inline(always)]
+pub fn mated_in(ply: usize) -> Score {
+    -MATE + ply as Score
+}
+
+pub trait ScoreTrait {
+    fn is_mate(&self) -> bool;
+    fn mate_moves(&self) -> Option<isize>;
+    fn to_tt(&self, ply: usize) -> i16;
+    fn from_tt(score: i16, ply: usize) -> Self;
+    fn to_uci(&self) -> String;
+    fn to_wdl(&self) -> (usize, usize, usize);
+}
+
+impl ScoreTrait for Score {
+    #[inline(always)]
+    fn is_mate(&self) -> bool {
+        self.abs() >= MATE_BOUND
+    }
+
+    /// Moves (not plies) until mate, negative when the side to move gets mated.
+    #[inline(always)]
+    fn mate_moves(&self) -> Option<isize> {
+        match *self {
+            s if s >= MATE_BOUND => Some((MATE - s + 1) / 2),
+            s if s <= -MATE_BOUND => Some(-(MATE + s) / 2),
+            _ => None,
+        }
+    }
+
+    /// The TT is shared by every ply, so mate scores are stored relative to the node
+    /// (distance from this position) instead of relative to the root.
+    #[inline(always)]
+    fn to_tt(&self, ply: usize) -> i16 {
+        match *self {
+            s if s >= MATE_BOUND => (s + ply as Score) as i16,
+            s if s <= -MATE_BOUND => (s - ply as Score) as i16,
+            s => s as i16,
+        }
+    }
+
+    #[inline(always)]
+    fn from_tt(score: i16, ply: usize) -> Self {
+        match score as Score {
+            s if s >= MATE_BOUND => s - ply as Score,
+            s if s <= -MATE_BOUND => s + ply as Score,
+            s => s,
+        }
+    }
+
+    fn to_uci(&self) -> String {
+        match self.mate_moves() {
+            Some(moves) => format!("mate {}", moves),
+            None => format!("cp {}", self),
+        }
+    }
+
+    /// Expected win, draw and loss in permille (UCI_ShowWDL). Each side wins with a logistic
+    /// chance centered `WDL_CENTER` centipawns in its favor, the draw takes the rest.
+    fn to_wdl(&self) -> (usize, usize, usize) {
+        match self.mate_moves() {
+            Some(moves) if moves > 0 => return (1000, 0, 0),
+            Some(_) => return (0, 0, 1000),
+            None => (),
+        }
+
+        let chance = |cp: Score| 1000.0 / (1.0 + (-(cp - WDL_CENTER) as f64 / WDL_SCALE).exp());
+        let (win, loss) = (chance(*self).round() as usize, chance(-*self).round() as usize);
+        (win, 1000 - win - loss, loss)
+    }
+}
+
+const WDL_CENTER: Score = 150;
+const WDL_SCALE: f64 = 60.0;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mate_moves() {
+        assert_eq!(mate_in(1).mate_moves(), Some(1));
+        assert_eq!(mate_in(5).mate_moves(), Some(3));
+        assert_eq!(mated_in(2).mate_moves(), Some(-1));
+        assert_eq!(mated_in(4).mate_moves(), Some(-2));
+        assert_eq!((150 as Score).mate_moves(), None);
+
+        assert_eq!(mate_in(3).to_uci(), "mate 2");
+        assert_eq!(mated_in(6).to_uci(), "mate -3");
+        assert_eq!((-35 as Score).to_uci(), "cp -35");
+    }
+
+    #[test]
+    fn test_wdl() {
+        let (win, draw, loss) = (0 as Score).to_wdl();
+        assert_eq!(win, loss);
+        assert!(draw > 800);
+
+        assert_eq!((150 as Score).to_wdl().0, 500);
+        let (win, draw, loss) = (-400 as Score).to_wdl();
+        assert!(loss > 950 && win == 0 && win + draw + loss == 1000);
+
+        assert_eq!(mate_in(3).to_wdl(), (1000, 0, 0));
+        assert_eq!(mated_in(4).to_wdl(), (0, 0, 1000));
+    }
+
+    #[test]
+    fn test_tt_round_trip() {
+        // Mate found 7 plies from the root at a node 4 plies deep: 3 plies from that node
+        let score = mate_in(7);
+        let stored = score.to_tt(4);
+        assert_eq!(stored as Score, mate_in(3));
+
+        // Reached again at ply 2, the mate is now 5 plies from the root
+        assert_eq!(Score::from_tt(stored, 2), mate_in(5));
+        assert_eq!(Score::from_tt(mated_in(9).to_tt(3), 3), mated_in(9));
+        assert_eq!(Score::from_tt((120 as Score).to_tt(10), 3), 120);
+    }
+}
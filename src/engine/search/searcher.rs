@@ -1,64 +1,107 @@
 use crate::engine::{
-    evaluation::evaluation::Evaluation,
+    evaluation::{evaluation::Evaluation, see::see_ge},
     game::Game,
     move_generation::{
+        legal_mv_gen::gen_legal_moves,
         make_move::GameMoveTrait,
-        mv_gen::{gen_captures, gen_moves, is_repetition, sq_attack},
+        mv_gen::{is_repetition, sq_attack},
     },
-    search::transposition_table::get_line,
     shared::{
         helper_func::{
             bitboard::BitboardTrait,
-            print_utility::{get_move_list, move_notation, print_chess, print_move_list},
+            print_utility::{
+                get_move_list, move_notation, print_chess, print_move_list, uci_move_notation,
+            },
         },
         structures::{
-            internal_move::{PositionIrr, PositionRev},
-            piece::{PieceTrait, KING},
+            internal_move::{Flag, PositionIrr, PositionRev},
+            piece::{PieceTrait, KING, PAWN},
         },
     },
 };
 use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, LazyLock,
+    },
+    thread,
     time::{Duration, Instant},
     u64,
 };
 
 use super::{
-    time::{safe_to_start_next_iter, time_over},
-    transposition_table::Bound,
+    history::prev_moves,
+    move_picker::MovePicker,
+    score::{mate_in, mated_in, Score, ScoreTrait, INF},
+    time::{nodes_over, safe_to_start_next_iter, time_over, TimeManager},
+    transposition_table::{Bound, TTData, TTEntry, TTTable},
 };
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct SearchInfo {
     pub start_time: Instant,
-    pub time_limit: Option<Duration>,
+    // None without a clock (infinite, go depth, go nodes ...)
+    pub time: Option<TimeManager>,
 
     pub depth: Option<u8>,
+    // Stop after this many nodes (go nodes), or once a mate in this many moves is found (go mate)
+    pub node_limit: Option<usize>,
+    pub mate: Option<usize>,
+    // Only these root moves are searched when not empty (go searchmoves)
+    pub search_moves: Vec<PositionRev>,
 
     pub moves_played: usize,
     pub moves_togo: usize,
     pub infinite: bool,
 
     pub nodes: usize,
+    // The nodes of the other threads as of the last checkup, the node budget is checked against
+    // it at every node without touching the shared counters
+    pub other_nodes: usize,
     pub curr_depth: u8,
+    // Highest ply reached, quiescence search and extensions included
+    pub sel_depth: usize,
     pub curr_key: u64,
+    // When the last info line, bound line and currmove line were printed, so they don't flood
+    // the GUI. Bound lines have their own, so they never hold back the line of an iteration.
+    pub last_info: Option<Instant>,
+    pub last_bound_info: Option<Instant>,
+    pub last_currmove: Option<Instant>,
 
     pub quit: bool,
     pub stopped: bool,
 
     pub fail_hard: usize,
     pub fail_hard_first: usize,
+
+    // Lazy SMP, the main thread is 0
+    pub thread_id: usize,
+    pub shared: Arc<SharedInfo>,
+
+    // Lines searched at the root, and the root moves sorted by the score of the last iteration
+    pub multi_pv: usize,
+    pub root_moves: Vec<RootMove>,
+    // UCI_ShowWDL, the info lines carry the expected win/draw/loss per mille
+    pub show_wdl: bool,
 }
 
 impl SearchInfo {
     pub fn init() -> Self {
         Self {
             start_time: Instant::now(),
-            time_limit: None,
+            time: None,
             depth: None,
+            node_limit: None,
+            mate: None,
+            search_moves: Vec::new(),
 
             curr_depth: 0,
+            sel_depth: 0,
             // DEPRECATE: It is not used
             curr_key: 0,
+            last_info: None,
+            last_bound_info: None,
+            last_currmove: None,
 
             moves_played: 0,
 
@@ -66,81 +109,243 @@ impl SearchInfo {
             infinite: false,
 
             nodes: 0,
+            other_nodes: 0,
 
             quit: false,
             stopped: false,
 
             fail_hard: 0,
             fail_hard_first: 0,
+
+            thread_id: 0,
+            // Replaced by lazy_smp, the table only has to exist
+            shared: Arc::new(SharedInfo::init(
+                1,
+                Arc::new(TTTable::with_buckets(1)),
+                Arc::new(AtomicBool::new(false)),
+                Arc::new(AtomicBool::new(false)),
+            )),
+
+            multi_pv: 1,
+            show_wdl: false,
+            root_moves: Vec::new(),
         }
     }
 }
 
+/// A legal move at the root with the score and the line it got in the last search of it, its
+/// score at the end of the previous iteration, and the nodes spent on it over all iterations.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RootMove {
+    pub rev: PositionRev,
+    pub score: Score,
+    pub prev_score: Score,
+    pub pv: Vec<PositionRev>,
+    pub nodes: usize,
+}
+
+impl RootMove {
+    pub fn init(rev: PositionRev) -> Self {
+        Self { rev, score: -INF, prev_score: -INF, pv: vec![rev], nodes: 0 }
+    }
+}
+
+/// State shared by every thread of one search.
+#[derive(Debug)]
+pub struct SharedInfo {
+    // Stops the helper threads once the main thread is done
+    pub stop: AtomicBool,
+    // Set from outside the search (UCI stop/quit), stops every thread
+    pub cancel: Arc<AtomicBool>,
+    // Set from outside the search while pondering (go ponder until ponderhit), the clock is
+    // ignored while it is set
+    pub ponder: Arc<AtomicBool>,
+    // Nodes searched by each thread, published every few thousand nodes
+    nodes: Vec<AtomicUsize>,
+    // Owned by the caller, so it outlives the search
+    pub tt: Arc<TTTable>,
+}
+
+impl SharedInfo {
+    pub fn init(
+        threads: usize,
+        tt: Arc<TTTable>,
+        cancel: Arc<AtomicBool>,
+        ponder: Arc<AtomicBool>,
+    ) -> Self {
+        Self {
+            stop: AtomicBool::new(false),
+            cancel,
+            ponder,
+            nodes: (0..threads).map(|_| AtomicUsize::new(0)).collect(),
+            tt,
+        }
+    }
+
+    pub fn total_nodes(&self) -> usize {
+        self.nodes.iter().map(|nodes| nodes.load(Ordering::Relaxed)).sum()
+    }
+}
+
+impl PartialEq for SharedInfo {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl Eq for SharedInfo {}
+
 pub fn check_time_up() {
     todo!();
 }
 
 pub fn clear_search(game: &mut Game) {
     game.s_killers.iter_mut().for_each(|arr| arr.fill(None));
-    game.s_history.iter_mut().for_each(|arr| arr.fill(0));
+    game.s_history.clear();
+    game.s_moves.fill(None);
+    game.s_excluded.fill(None);
+    game.s_extensions.fill(0);
     game.ply = 0;
 
     game.info.start_time = Instant::now();
     game.info.stopped = false;
     game.info.nodes = 0;
+    game.info.other_nodes = 0;
     game.info.curr_key = game.key;
     game.info.curr_depth = 0;
+    game.info.sel_depth = 0;
+    game.info.last_info = None;
+    game.info.last_bound_info = None;
+    game.info.last_currmove = None;
+}
 
-    game.tt.collisions = 0;
-    game.tt.hits = 0;
-    game.tt.inserts = 0;
-    game.tt.lookups = 0;
+/// Every 2048 nodes, or as soon as the node budget looks spent with the other threads where they
+/// were at the last checkup.
+#[inline(always)]
+fn checkup_due(game: &Game) -> bool {
+    (game.info.nodes & 2047) == 0 || nodes_over(game)
 }
 
-fn quiescence_search(mut alpha: isize, beta: isize, game: &mut Game) -> isize {
-    let eval = game.evaluate_pos();
-    if eval >= beta {
-        return beta;
+/// Publishes the node count of this thread, refreshes the count of the others under a node
+/// budget, and checks if the search has to stop. Once it has,
+/// `stopped` stays set so every node on the way back up sees it, not only the ones that land on
+/// a checkup.
+fn checkup(game: &mut Game) -> bool {
+    game.info.shared.nodes[game.info.thread_id].store(game.info.nodes, Ordering::Relaxed);
+    if game.info.node_limit.is_some() {
+        game.info.other_nodes = game.info.shared.total_nodes() - game.info.nodes;
     }
+    game.info.stopped = time_over(game);
+    game.info.stopped
+}
 
+/// Resolves the tactics left at the horizon, so that the static eval is only trusted in quiet
+/// positions. In check every evasion is searched and there is no stand pat, otherwise only the
+/// captures that can still reach alpha, followed by the quiet checks when `checks` is set (the
+/// first ply of the quiescence search).
+fn quiescence_search(mut alpha: Score, beta: Score, checks: bool, game: &mut Game) -> Score {
     game.info.nodes += 1;
 
-    alpha = alpha.max(eval);
+    if game.ply >= MAX_PLY {
+        return game.evaluate_pos();
+    }
+    game.s_pv.clear(game.ply);
+    game.info.sel_depth = game.info.sel_depth.max(game.ply);
 
-    let (irr, mut pos_rev) = gen_captures(game.color, game);
+    if let Some(score) = game.info.shared.tt.probe(game.key, 0, game.ply, alpha, beta) {
+        return score;
+    }
 
-    for rev in &mut pos_rev {
-        if (game.info.nodes & 2047) == 0 && time_over(game) {
-            break;
+    let irr = PositionIrr::init_with_game(game);
+    let tt_entry = game.info.shared.tt.get(game.key);
+    let tt_mv = tt_entry.and_then(|e| e.rev(game));
+    let mut picker = MovePicker::init_captures(game, tt_mv, checks);
+    let in_check = picker.masks.in_check();
+
+    let eval = match (in_check, tt_entry) {
+        (true, _) => -INF,
+        (false, Some(e)) => e.eval as Score,
+        (false, None) => game.evaluate_pos(),
+    };
+
+    // Stand pat: the side to move can (usually) do at least as well as the static eval, except
+    // when in check where the position is anything but quiet.
+    if in_check {
+        picker = MovePicker::init(game, tt_mv);
+    } else {
+        if eval >= beta {
+            return beta;
+        }
+        alpha = alpha.max(eval);
+    }
+
+    let old_alpha = alpha;
+    let mut best_mv = None;
+    let mut legal_mv_num = 0;
+
+    while let Some(rev) = picker.next(game) {
+        if game.info.stopped || (checkup_due(game) && checkup(game)) {
+            return 0;
         }
 
-        if !game.make_move(rev, &irr) {
+        // Delta Pruning: skip the captures that can't get the eval back to alpha, first by the
+        // value of the captured piece and then by the whole exchange.
+        let gap = alpha - eval - DELTA_MARGIN;
+        if !in_check
+            && gap > 0
+            && !matches!(rev.flag, Flag::Promotion(..))
+            && (captured_value(&rev) < gap || !see_ge(game, &rev, gap))
+        {
             continue;
         }
-        let score = -quiescence_search(-beta, -alpha, game);
+
+        game.s_moves[game.ply] = Some(rev);
+        game.make_legal_move(&rev, &irr);
+        legal_mv_num += 1;
+
+        let score = -quiescence_search(-beta, -alpha, false, game);
         game.undo_move();
 
-        if score >= beta {
-            return beta;
+        if game.info.stopped {
+            return 0;
         }
 
-        alpha = alpha.max(score);
+        if score > alpha {
+            if score >= beta {
+                let data = TTData { rev, score: beta, eval, depth: 0, bound: Bound::Lower };
+                game.info.shared.tt.set(game.key, game.ply, data);
+                return beta;
+            }
+            alpha = score;
+            best_mv = Some(rev);
+        }
+    }
+
+    if in_check && legal_mv_num == 0 {
+        return mated_in(game.ply);
+    }
+
+    if let Some(mv) = best_mv {
+        let bound = if alpha > old_alpha { Bound::Exact } else { Bound::Upper };
+        let data = TTData { rev: mv, score: alpha, eval, depth: 0, bound };
+        game.info.shared.tt.set(game.key, game.ply, data);
     }
 
     alpha
 }
 
 fn alpha_beta(
-    mut alpha: isize,
-    mut beta: isize,
+    mut alpha: Score,
+    mut beta: Score,
     depth: u8,
-    pv: &mut Vec<PositionRev>,
     game: &mut Game,
     take_null: bool,
-) -> isize {
+) -> Score {
+    game.s_pv.clear(game.ply);
+
     // If we reached the final depth than make sure there is no horizon effect
     if depth == 0 {
-        return quiescence_search(alpha, beta, game);
+        return quiescence_search(alpha, beta, true, game);
     }
 
     game.info.nodes += 1;
@@ -150,126 +355,665 @@ fn alpha_beta(
         return 0;
     }
 
-    if let Some(score) = game.tt.probe(game.key, depth, alpha as i16, beta as i16) {
-        game.tt.hits += 1;
-        return score as isize;
+    if game.ply >= MAX_PLY - 1 {
+        return game.evaluate_pos();
     }
+    game.info.sel_depth = game.info.sel_depth.max(game.ply);
+
+    // The move left out by a singular extension search, this node is only a part of the real one
+    let excluded = game.s_excluded[game.ply];
+    let extensions = game.s_extensions[game.ply];
+
+    // Mate Distance Pruning: nothing found from here can beat a mate that is already known
+    // closer to the root.
+    if game.ply > 0 {
+        alpha = alpha.max(mated_in(game.ply));
+        beta = beta.min(mate_in(game.ply + 1));
+        if alpha >= beta {
+            return alpha;
+        }
+    }
+
+    let pv_node = beta - alpha > 1;
 
+    // No cutoff in PV nodes, the TT persists between searches and a cutoff would leave the
+    // line of the node empty
+    if !pv_node && excluded.is_none() {
+        if let Some(score) = game.info.shared.tt.probe(game.key, depth, game.ply, alpha, beta) {
+            return score;
+        }
+    }
     let mut best_mv = None;
     let mut best_score = alpha;
     let mut legal_mv_num = 0;
-    let old_alpha: isize = alpha;
+    let old_alpha: Score = alpha;
+    let mut quiets: Vec<PositionRev> = Vec::with_capacity(32);
+    let mut captures: Vec<PositionRev> = Vec::with_capacity(16);
+
+    let irr = PositionIrr::init_with_game(game);
+    let tt_entry = game.info.shared.tt.get(game.key);
+    let tt_mv = tt_entry.and_then(|e| e.rev(game));
+    let mut picker = MovePicker::init(game, tt_mv);
+    let in_check = picker.masks.in_check();
+    let prev = prev_moves(game);
+    let counter = game.s_history.counter_move(prev[0]);
+
+    // Static eval, only needed (and only trusted) for the selective pruning below. A TT hit
+    // already has it.
+    let eval = match (in_check, tt_entry) {
+        (true, _) => -INF,
+        (false, Some(e)) => e.eval as Score,
+        (false, None) => game.evaluate_pos(),
+    };
+    let can_prune = !pv_node && !in_check && game.ply > 0 && excluded.is_none();
+
+    // Reverse Futility Pruning (static null move): the eval is so far above beta that a
+    // shallow search is not expected to bring it back down.
+    if can_prune && depth <= RFP_MAX_DEPTH && eval - RFP_MARGIN * depth as isize >= beta {
+        return eval;
+    }
 
-    let (irr, pos_rev) = gen_moves(game.color, game);
+    // Razoring: far below alpha at low depth, only captures can save the position.
+    if can_prune && depth <= RAZOR_MAX_DEPTH && eval + RAZOR_MARGIN * (depth as isize) < alpha {
+        let score = quiescence_search(alpha, beta, true, game);
+        if score < alpha {
+            return score;
+        }
+    }
 
-    for rev in &pos_rev {
-        // Check Time every 2027 Nodes
-        if (game.info.nodes & 2047) == 0 && time_over(game) {
+    // Null Move Pruning: if passing the turn still fails high, the position is good enough to
+    // cut. Skipped in check, right after another null move and with only pawns left
+    // (zugzwang), the reduction grows with depth and with how far the eval is above beta. The
+    // null search keeps at least two plies, otherwise a quiet mate threat is hidden in qsearch.
+    if take_null
+        && game.ply > 0
+        && depth >= NULL_MIN_DEPTH
+        && !in_check
+        && eval >= beta
+        && has_non_pawn_material(game)
+    {
+        let r = NULL_BASE_R + depth / NULL_DEPTH_DIV + ((eval - beta) / NULL_EVAL_DIV).min(2) as u8;
+        let null_depth = depth.saturating_sub(1 + r).max(NULL_MIN_SEARCH_DEPTH);
+
+        game.s_moves[game.ply] = None;
+        game.make_null_move();
+        game.s_extensions[game.ply] = extensions;
+        let score = -alpha_beta(-beta, -beta + 1, null_depth, game, false);
+        game.undo_null_move();
+
+        // At high depth the cutoff is verified by a reduced search without null moves
+        if score >= beta
+            && (depth < NULL_VERIFY_DEPTH
+                || alpha_beta(beta - 1, beta, null_depth, game, false) >= beta)
+        {
+            return beta;
+        }
+    }
+
+    while let Some(rev) = picker.next(game) {
+        if game.info.stopped || (checkup_due(game) && checkup(game)) {
             return 0;
         }
 
-        if !game.make_move(rev, &irr) {
+        if Some(rev) == excluded {
             continue;
         }
+
+        // Singular Extension: the TT move is the only good move if all the others fail low
+        // against a margin below its score in a reduced search.
+        let singular = extensions < MAX_PATH_EXTENSIONS
+            && game.ply > 0
+            && depth >= SE_MIN_DEPTH
+            && excluded.is_none()
+            && Some(rev) == tt_mv
+            && tt_entry.is_some_and(|e| is_singular(game, &rev, e, depth));
+
+        let is_refutation = game.s_killers[game.ply].contains(&Some(rev)) || Some(rev) == counter;
+        let history = game.s_history.quiet_score(&rev, &prev);
+
+        let prunable = can_prune && legal_mv_num > 0 && rev.flag.is_quiet();
+        let see_prune = prunable
+            && depth <= SEE_QUIET_MAX_DEPTH
+            && !see_ge(game, &rev, -SEE_QUIET_MARGIN * depth as isize);
+
+        // Recapture Extension: keeps the exchange on one square together in the PV
+        let recapture = pv_node
+            && rev.flag.is_capture()
+            && prev[0].is_some_and(|p| p.flag.is_capture() && p.to == rev.to);
+
+        game.s_moves[game.ply] = Some(rev);
+        game.make_legal_move(&rev, &irr);
         legal_mv_num += 1;
-        let mut node_pv: Vec<PositionRev> = Vec::new();
-        let score = -alpha_beta(-beta, -alpha, depth - 1, &mut node_pv, game, true);
+
+        let check = gives_check(game);
+
+        // Late Move, Futility and SEE Pruning of quiet moves that don't give check
+        if prunable
+            && (see_prune
+                || late_move_prune(depth, legal_mv_num)
+                || futility_prune(depth, eval, alpha))
+            && !check
+        {
+            game.undo_move();
+            continue;
+        }
+
+        // Extensions: checks, singular moves and recaptures are searched a ply deeper, so the
+        // forcing lines are followed past the horizon, up to the budget of this path.
+        let extension =
+            (extensions < MAX_PATH_EXTENSIONS && (singular || check || recapture)) as u8;
+        game.s_extensions[game.ply] = extensions + extension;
+        let new_depth = depth - 1 + extension;
+
+        // Principal Variation Search: only the first move gets the full window, the rest are
+        // expected to fail low and are proven with a (reduced) zero window search first.
+        let score = match legal_mv_num {
+            1 => -alpha_beta(-beta, -alpha, new_depth, game, true),
+            _ => {
+                let r = match depth >= LMR_MIN_DEPTH
+                    && legal_mv_num > LMR_MIN_MOVES
+                    && rev.flag.is_quiet()
+                    && !in_check
+                {
+                    true => {
+                        lmr_reduction(depth, legal_mv_num, pv_node, is_refutation, history, game)
+                    }
+                    false => 0,
+                };
+
+                let mut score = -alpha_beta(-alpha - 1, -alpha, new_depth - r, game, true);
+                if score > alpha && r > 0 {
+                    score = -alpha_beta(-alpha - 1, -alpha, new_depth, game, true);
+                }
+                if score > alpha && score < beta {
+                    score = -alpha_beta(-beta, -alpha, new_depth, game, true);
+                }
+                score
+            }
+        };
         game.undo_move();
 
+        // The score of an aborted search is garbage, it must not reach the TT
+        if game.info.stopped {
+            return 0;
+        }
+
         if score > alpha {
             if score >= beta {
-                if !rev.flag.is_capture() {
-                    game.s_killers[game.ply][0] = game.s_killers[game.ply][1];
-                    game.s_killers[game.ply][1] = Some(*rev);
+                match rev.flag.is_capture() {
+                    true => game.s_history.update_captures(Some(&rev), &captures, depth),
+                    false => {
+                        game.s_killers[game.ply][0] = game.s_killers[game.ply][1];
+                        game.s_killers[game.ply][1] = Some(rev);
+                        game.s_history.update_quiets(&rev, &quiets, &prev, depth);
+                        game.s_history.update_captures(None, &captures, depth);
+                    }
+                }
+                if excluded.is_none() {
+                    let data = TTData { rev, score, eval, depth, bound: Bound::Lower };
+                    game.info.shared.tt.set(game.key, game.ply, data);
                 }
-                game.tt.set(game.key, *rev, score as i16, depth, Bound::Upper);
 
                 return score;
             }
 
-            pv.clear();
-            pv.push(*rev);
-            pv.append(&mut node_pv);
+            game.s_pv.update(game.ply, rev);
             alpha = score;
             best_score = score;
             best_mv = Some(rev);
+        }
 
-            if !rev.flag.is_capture() {
-                game.s_history[rev.piece.idx()][rev.to as usize] += (depth * depth) as u64;
-            }
+        // The moves that didn't cut get a malus once another one does
+        match rev.flag.is_capture() {
+            true => captures.push(rev),
+            false => quiets.push(rev),
         }
     }
 
-    // Checking for if the position is draw or checkmate
+    // Checking for if the position is draw or checkmate, unless the only move was excluded
     if legal_mv_num == 0 {
-        let king_sq = game.bitboard[(KING + game.color) as usize].get_lsb();
-        return match sq_attack(game, king_sq, game.color) != 0 {
-            true => -1000000 + (game.ply as isize),
-            false => 0,
+        return match (excluded, picker.masks.in_check()) {
+            (Some(_), _) => alpha,
+            (None, true) => mated_in(game.ply),
+            (None, false) => 0,
         };
     }
 
-    if let Some(mv) = best_mv {
+    if let (Some(mv), None) = (best_mv, excluded) {
         let bound = if best_score > old_alpha { Bound::Exact } else { Bound::Upper };
-        game.tt.set(game.key, *mv, alpha as i16, depth, bound);
+        let data = TTData { rev: mv, score: alpha, eval, depth, bound };
+        game.info.shared.tt.set(game.key, game.ply, data);
     }
 
     alpha
 }
 
-pub fn iterative_deepening(game: &mut Game) -> Option<PositionRev> {
+/// A search on the calling thread alone, with the table of the caller.
+pub fn iterative_deepening(game: &mut Game, tt: &Arc<TTTable>) -> Option<PositionRev> {
+    lazy_smp(game, 1, tt, &Arc::default(), &Arc::default())
+}
+
+/// Lazy SMP: the helper threads search the same root on their own copy of the game, so they
+/// keep their own killers and history and only share the TT. When the main thread is done the
+/// helpers are stopped, and the move of the deepest completed iteration is played (the main
+/// thread wins ties). The entries the search leaves in `tt` are kept for the next one, and
+/// setting `cancel` stops the search within a few thousand nodes. While `ponder` is set the
+/// time limit is not checked, clearing it turns the search into a timed one.
+pub fn lazy_smp(
+    game: &mut Game,
+    threads: usize,
+    tt: &Arc<TTTable>,
+    cancel: &Arc<AtomicBool>,
+    ponder: &Arc<AtomicBool>,
+) -> Option<PositionRev> {
+    tt.new_search();
+    let (cancel, ponder) = (Arc::clone(cancel), Arc::clone(ponder));
+    let shared = SharedInfo::init(threads.max(1), Arc::clone(tt), cancel, ponder);
+    game.info.shared = Arc::new(shared);
+
+    let helpers: Vec<_> = (1..threads)
+        .map(|id| {
+            let mut helper = game.clone();
+            helper.info.thread_id = id;
+            thread::spawn(move || search_root(&mut helper))
+        })
+        .collect();
+
+    let mut best = search_root(game);
+    game.info.shared.stop.store(true, Ordering::Relaxed);
+
+    for helper in helpers {
+        match helper.join() {
+            Ok((depth, Some(mv))) if depth > best.0 => best = (depth, Some(mv)),
+            Ok(_) => (),
+            Err(e) => eprintln!("info string Search helper panicked: {:?}", e),
+        }
+    }
+
+    best.1
+}
+
+/// The expected reply to `best`: the second move of its line, or the TT move after it when
+/// the line is too short (or `best` came from a helper thread).
+pub fn ponder_move(game: &mut Game, best: &PositionRev) -> Option<PositionRev> {
+    if let Some(rm) = game.info.root_moves.first().filter(|rm| rm.rev == *best) {
+        if rm.pv.len() > 1 {
+            return Some(rm.pv[1]);
+        }
+    }
+
+    game.make_legal_move(best, &PositionIrr::init_with_game(game));
+    let reply = game.info.shared.tt.get(game.key).and_then(|e| e.rev(game));
+    let reply = reply.filter(|rev| gen_legal_moves(game).1.contains(rev));
+    game.undo_move();
+    reply
+}
+
+/// Iterative deepening on one thread, returns the depth of the last completed iteration
+/// with its best move. Odd helper threads search one ply deeper than the iteration they are
+/// on, so the threads don't all walk the same tree. With MultiPV every iteration searches the
+/// best `multi_pv` lines one after the other, each without the moves of the lines before it.
+fn search_root(game: &mut Game) -> (u8, Option<PositionRev>) {
     clear_search(game);
 
-    let mut alpha = MIN_INF;
-    let mut beta = MAX_INF;
-    let mut best_mv = None;
-    let mut root_pv: Vec<PositionRev> = Vec::new();
+    let mut best = (0, None);
 
-    for depth in 1..game.info.depth.unwrap_or(20) + 1 {
-        if !safe_to_start_next_iter(game) {
+    let (_, mut moves) = gen_legal_moves(game);
+    if !game.info.search_moves.is_empty() {
+        moves.retain(|rev| game.info.search_moves.contains(rev));
+    }
+    let mut root_moves: Vec<RootMove> = moves.into_iter().map(RootMove::init).collect();
+    // The order and scores of an aborted iteration are partial, the last completed one is kept
+    let mut completed = root_moves.clone();
+    let lines = game.info.multi_pv.clamp(1, root_moves.len().max(1));
+
+    let max_depth = game.info.depth.unwrap_or(20);
+    let depth_offset = (game.info.thread_id % 2) as u8;
+
+    // How settled the search is, it decides how much of the optimum time is used
+    let mut best_changes = 0.0;
+    let mut prev_score = None;
+    let mut time_scale = 1.0;
+    // Depth of the last iteration the GUI was told about, seldepth of the last completed one
+    let mut printed = 0;
+    let mut completed_sel_depth = 0;
+
+    for depth in 1..max_depth + 1 {
+        // Helpers run until the main thread is done or the hard limit is hit
+        let next_iter = match game.info.thread_id {
+            0 => safe_to_start_next_iter(game, time_scale),
+            _ => !time_over(game),
+        };
+        if !next_iter || root_moves.is_empty() {
             break;
         }
 
+        // The last iteration of an odd helper would repeat the one before it
+        if depth + depth_offset > max_depth {
+            break;
+        }
+        let depth = depth + depth_offset;
         set_curr_depth(game, depth);
-        let score = alpha_beta(alpha, beta, depth, &mut root_pv, game, true);
+        game.info.sel_depth = 0;
+        // The lines after the first can lose their scores before they are searched themselves
+        root_moves.iter_mut().for_each(|rm| rm.prev_score = rm.score);
+
+        for pv_idx in 0..lines {
+            aspiration_search(depth, pv_idx, game, &mut root_moves);
+            if time_over(game) {
+                break;
+            }
+        }
 
         if time_over(game) {
             break;
         }
+        root_moves[..lines].sort_by_key(|rm| -rm.score);
+
+        let (rev, score) = (root_moves[0].rev, root_moves[0].score);
+
+        best_changes = best_changes / 2.0 + (best.1.is_some_and(|mv| mv != rev) as u8 as f64);
+        let score_drop = prev_score.map_or(0, |prev: Score| prev - score);
+        let total_nodes: usize = root_moves.iter().map(|rm| rm.nodes).sum();
+        let best_effort = root_moves[0].nodes as f64 / total_nodes.max(1) as f64;
+        time_scale = TimeManager::scale(best_changes, score_drop, best_effort);
+        prev_score = Some(score);
+        best = (depth, Some(rev));
+        completed = root_moves.clone();
+        completed_sel_depth = game.info.sel_depth;
+
+        let eval = game.evaluate_pos();
+        let data = TTData { rev, score, eval, depth, bound: Bound::Exact };
+        game.info.shared.tt.set(game.key, game.ply, data);
+
+        checkup(game);
+        if game.info.thread_id == 0 && info_due(&mut game.info.last_info, INFO_INTERVAL) {
+            print_lines(game, &completed[..lines]);
+            printed = depth;
+        }
+
+        if mate_found(game, score) {
+            break;
+        }
+    }
 
-        // Adjust alpha and beta using aspiration window
-        (alpha, beta) = aspiration_window(alpha, beta, score, depth);
+    // The GUI gets the result of the last iteration even if it came too fast to be printed
+    if game.info.thread_id == 0 && best.0 > printed {
+        set_curr_depth(game, best.0);
+        game.info.sel_depth = completed_sel_depth;
+        print_lines(game, &completed[..lines]);
+    }
+
+    // Stopped before the first iteration was done, any legal move is better than none
+    if best.1.is_none() {
+        best.1 = completed.first().map(|rm| rm.rev);
+    }
+
+    game.info.root_moves = completed;
+    best
+}
 
-        // Get Best Line from current position and print info
-        if root_pv.len() > 0 {
-            best_mv = Some(root_pv[0]);
+/// Principal variation search over the root moves from `pv_idx` on, in the order of the last
+/// iteration. The moves that don't raise alpha get `-INF`, so the stable sort afterwards keeps
+/// them in their old order behind the ones that did.
+fn search_root_moves(
+    mut alpha: Score,
+    beta: Score,
+    depth: u8,
+    game: &mut Game,
+    root_moves: &mut [RootMove],
+    pv_idx: usize,
+) -> Score {
+    game.info.nodes += 1;
+
+    let irr = PositionIrr::init_with_game(game);
+    let root_moves = &mut root_moves[pv_idx..];
+    for (i, rm) in root_moves.iter_mut().enumerate() {
+        let nodes = game.info.nodes;
+
+        // Long searches tell the GUI which move is being looked at now and then
+        if game.info.thread_id == 0
+            && game.info.start_time.elapsed() >= VERBOSE_INFO_DELAY
+            && info_due(&mut game.info.last_currmove, CURRMOVE_INTERVAL)
+        {
+            let mv = uci_move_notation(&rm.rev, game);
+            println!("info depth {} currmove {} currmovenumber {}", depth, mv, pv_idx + i + 1);
         }
 
-        print_info(game, score, get_move_list(&root_pv));
+        game.s_moves[game.ply] = Some(rm.rev);
+        game.make_legal_move(&rm.rev, &irr);
+        let mut score = match i {
+            0 => -alpha_beta(-beta, -alpha, depth - 1, game, true),
+            _ => -alpha_beta(-alpha - 1, -alpha, depth - 1, game, true),
+        };
+        if i > 0 && score > alpha && score < beta {
+            score = -alpha_beta(-beta, -alpha, depth - 1, game, true);
+        }
+        game.undo_move();
+        rm.nodes += game.info.nodes - nodes;
+
+        if time_over(game) {
+            break;
+        }
+
+        match i == 0 || score > alpha {
+            true => {
+                rm.score = score;
+                rm.pv = vec![rm.rev];
+                rm.pv.extend_from_slice(game.s_pv.line(game.ply + 1));
+                alpha = alpha.max(score);
+            }
+            false => rm.score = -INF,
+        }
+
+        if score >= beta {
+            break;
+        }
     }
 
-    best_mv
+    root_moves.sort_by_key(|rm| -rm.score);
+    root_moves[0].score
 }
 
-fn aspiration_window(alpha: isize, beta: isize, score: isize, depth: u8) -> (isize, isize) {
-    match depth < MIN_ASP_WINDOW_DEPTH || (score <= alpha) || (score >= beta) {
-        true => (MIN_INF, MAX_INF),
-        false => (score - 30, score + 30),
+/// True once the search proved a mate within the moves asked for by `go mate`.
+fn mate_found(game: &Game, score: Score) -> bool {
+    match (game.info.mate, score.mate_moves()) {
+        (Some(limit), Some(moves)) => moves > 0 && moves as usize <= limit,
+        _ => false,
+    }
+}
+
+/// Searches one line in a window around the score it had in the previous iteration. A result
+/// outside the window is only a bound, so the same depth is searched again with the failing
+/// side widened, by a step that grows each time, until the score lands inside.
+fn aspiration_search(depth: u8, pv_idx: usize, game: &mut Game, root_moves: &mut [RootMove]) {
+    let prev = root_moves[pv_idx].prev_score;
+    let mut delta = ASP_WINDOW;
+    let (mut alpha, mut beta) = match depth >= MIN_ASP_WINDOW_DEPTH && !prev.is_mate() {
+        true => ((prev - delta).max(-INF), (prev + delta).min(INF)),
+        false => (-INF, INF),
+    };
+
+    loop {
+        let score = search_root_moves(alpha, beta, depth, game, root_moves, pv_idx);
+        if time_over(game) {
+            return;
+        }
+
+        let bound = match score {
+            s if s <= alpha && alpha > -INF => Bound::Upper,
+            s if s >= beta && beta < INF => Bound::Lower,
+            _ => return,
+        };
+
+        // Only worth showing once the re-searches take a while
+        if game.info.thread_id == 0
+            && game.info.start_time.elapsed() >= VERBOSE_INFO_DELAY
+            && info_due(&mut game.info.last_bound_info, INFO_INTERVAL)
+        {
+            let line = info_line(game, pv_idx + 1, score, bound, &root_moves[pv_idx].pv);
+            println!("{}", line);
+        }
+
+        (alpha, beta) = widen_window(alpha, beta, score, delta);
+        delta *= 2;
+    }
+}
+
+/// Moves the side of the window the score fell out of to `delta` beyond the score.
+fn widen_window(alpha: Score, beta: Score, score: Score, delta: Score) -> (Score, Score) {
+    match score <= alpha {
+        true => ((score - delta).max(-INF), beta),
+        false => (alpha, (score + delta).min(INF)),
     }
-    // (MIN_INF, MAX_INF)
 }
 
 // FIXME: NOTE: Some useful small functions
 
+/// Late move reductions, indexed by depth and move number: `base + ln(depth) * ln(moves) / div`.
+static LMR_TABLE: LazyLock<[[u8; 64]; 64]> = LazyLock::new(|| {
+    let mut table = [[0; 64]; 64];
+    for (depth, row) in table.iter_mut().enumerate().skip(1) {
+        for (mv_num, r) in row.iter_mut().enumerate().skip(1) {
+            *r = (LMR_BASE + (depth as f64).ln() * (mv_num as f64).ln() / LMR_DIVISOR) as u8;
+        }
+    }
+    table
+});
+
+/// Reduction of a late quiet move. PV nodes, killers, checking moves and moves with a good
+/// history are reduced less. The reduced search never goes below depth 1.
+#[inline(always)]
+fn lmr_reduction(
+    depth: u8,
+    mv_num: usize,
+    pv_node: bool,
+    is_refutation: bool,
+    history: i32,
+    game: &Game,
+) -> u8 {
+    let mut r = LMR_TABLE[(depth as usize).min(63)][mv_num.min(63)] as isize;
+
+    r -= pv_node as isize;
+    r -= is_refutation as isize;
+    r -= gives_check(game) as isize;
+    r -= (history / LMR_HISTORY_DIV).clamp(-2, 2) as isize;
+
+    r.clamp(0, depth as isize - 2) as u8
+}
+
+/// Searches the node without the TT move `rev` at half the depth, against a margin below the
+/// TT score. If every other move fails low there, `rev` is singular. Only a lower bound of
+/// about the same depth is trusted to be near the real score.
+fn is_singular(game: &mut Game, rev: &PositionRev, e: TTEntry, depth: u8) -> bool {
+    let tt_score = Score::from_tt(e.score, game.ply);
+    if e.category == Bound::Upper
+        || e.depth < depth.saturating_sub(SE_TT_DEPTH_MARGIN)
+        || tt_score.is_mate()
+    {
+        return false;
+    }
+
+    let singular_beta = tt_score - SE_MARGIN * depth as Score;
+    game.s_excluded[game.ply] = Some(*rev);
+    let score = alpha_beta(singular_beta - 1, singular_beta, (depth - 1) / 2, game, false);
+    game.s_excluded[game.ply] = None;
+
+    score < singular_beta && !game.info.stopped
+}
+
+/// Once enough quiet moves were searched at low depth, the remaining ones are skipped.
+#[inline(always)]
+fn late_move_prune(depth: u8, mv_num: usize) -> bool {
+    depth <= LMP_MAX_DEPTH && mv_num > LMP_BASE + (depth as usize * depth as usize)
+}
+
+/// Near the leaves a quiet move can't make up for an eval that is far below alpha.
+#[inline(always)]
+fn futility_prune(depth: u8, eval: Score, alpha: Score) -> bool {
+    depth <= FUTILITY_MAX_DEPTH && eval + FUTILITY_MARGIN * (depth as isize) <= alpha
+}
+
+/// Value of the piece a capture takes off the board.
+#[inline(always)]
+fn captured_value(rev: &PositionRev) -> Score {
+    match rev.flag {
+        Flag::Capture(cap) | Flag::Promotion(_, Some(cap)) => cap.weight(),
+        Flag::EP => PAWN.weight(),
+        _ => 0,
+    }
+}
+
+/// Called after the move is made, so the side to move is the one that might be in check.
+#[inline(always)]
+fn gives_check(game: &Game) -> bool {
+    let king_sq = game.bitboard(KING + game.color).get_lsb();
+    sq_attack(game, king_sq, game.color) != 0
+}
+
+/// False when the side to move has only its king and pawns, where zugzwang is likely.
+#[inline(always)]
+fn has_non_pawn_material(game: &Game) -> bool {
+    let color = game.color;
+    game.bitboard(color) & !(game.bitboard(PAWN + color) | game.bitboard(KING + color)) != 0
+}
+
 fn set_curr_depth(game: &mut Game, depth: u8) {
     game.info.curr_depth = depth;
 }
 
-fn print_info(game: &mut Game, score: isize, line: String) {
-    println!(
-        "info depth {} nodes {} score cp {} pv{}",
-        game.info.curr_depth, game.info.nodes, score, line
-    );
+/// Rate limit of the info lines, true (and `last` restarted) if one may be printed now.
+fn info_due(last: &mut Option<Instant>, interval: Duration) -> bool {
+    let due = last.is_none_or(|last| last.elapsed() >= interval);
+    if due {
+        *last = Some(Instant::now());
+    }
+    due
+}
+
+fn print_lines(game: &Game, root_moves: &[RootMove]) {
+    for (k, rm) in root_moves.iter().enumerate() {
+        println!("{}", info_line(game, k + 1, rm.score, Bound::Exact, &rm.pv));
+    }
+}
+
+/// The UCI info line of one PV. There are no tablebases, so tbhits is never sent.
+fn info_line(
+    game: &Game,
+    multi_pv: usize,
+    score: Score,
+    bound: Bound,
+    pv: &[PositionRev],
+) -> String {
+    let bound = match bound {
+        Bound::Lower => " lowerbound",
+        Bound::Exact => "",
+        Bound::Upper => " upperbound",
+    };
+    let time = game.info.start_time.elapsed().as_millis() as usize;
+    let nodes = game.info.shared.total_nodes();
+    let wdl = match game.info.show_wdl {
+        true => {
+            let (win, draw, loss) = score.to_wdl();
+            format!(" wdl {} {} {}", win, draw, loss)
+        }
+        false => String::new(),
+    };
+
+    format!(
+        "info depth {} seldepth {} multipv {} score {}{}{} nodes {} nps {} hashfull {} time {} pv{}",
+        game.info.curr_depth,
+        game.info.sel_depth.max(game.info.curr_depth as usize),
+        multi_pv,
+        score.to_uci(),
+        bound,
+        wdl,
+        nodes,
+        nodes * 1000 / time.max(1),
+        game.info.shared.tt.hashfull(),
+        time,
+        get_move_list(pv, game)
+    )
 }
 
 fn print_pruning_info(game: &mut Game, score: isize) {
@@ -286,17 +1030,424 @@ fn print_ordering_info(game: &mut Game) {
     );
 }
 
+pub const MAX_PLY: usize = 64;
+
+const INFO_INTERVAL: Duration = Duration::from_millis(100);
+const VERBOSE_INFO_DELAY: Duration = Duration::from_secs(1);
+const CURRMOVE_INTERVAL: Duration = Duration::from_millis(500);
+
 const MIN_ASP_WINDOW_DEPTH: u8 = 6;
-const MAX_INF: isize = isize::MAX / 2;
-const MIN_INF: isize = isize::MIN / 2;
+const ASP_WINDOW: Score = 30;
+
+const NULL_MIN_DEPTH: u8 = 3;
+const NULL_BASE_R: u8 = 3;
+const NULL_DEPTH_DIV: u8 = 4;
+const NULL_EVAL_DIV: isize = 200;
+const NULL_VERIFY_DEPTH: u8 = 8;
+const NULL_MIN_SEARCH_DEPTH: u8 = 2;
+
+const RFP_MAX_DEPTH: u8 = 6;
+const RFP_MARGIN: isize = 80;
+const RAZOR_MAX_DEPTH: u8 = 2;
+const RAZOR_MARGIN: isize = 300;
+const FUTILITY_MAX_DEPTH: u8 = 2;
+const FUTILITY_MARGIN: isize = 120;
+const LMP_MAX_DEPTH: u8 = 4;
+const LMP_BASE: usize = 4;
+const SEE_QUIET_MAX_DEPTH: u8 = 8;
+const SEE_QUIET_MARGIN: isize = 60;
+
+const DELTA_MARGIN: isize = 200;
+
+const MAX_PATH_EXTENSIONS: u8 = 16;
+const SE_MIN_DEPTH: u8 = 8;
+const SE_TT_DEPTH_MARGIN: u8 = 3;
+const SE_MARGIN: Score = 2;
+
+const LMR_MIN_DEPTH: u8 = 3;
+const LMR_MIN_MOVES: usize = 3;
+const LMR_BASE: f64 = 0.75;
+const LMR_DIVISOR: f64 = 2.25;
+const LMR_HISTORY_DIV: i32 = 8192;
 
 #[cfg(test)]
 mod tests {
     use std::fs::File;
 
-    use crate::engine::game::Game;
+    use crate::engine::{
+        fen::fen::FenTrait,
+        game::Game,
+        shared::{
+            helper_func::const_utility::{
+                FEN_MATE_IN_3, FEN_MATE_IN_4, FEN_MATE_IN_5, FEN_POS_TWO,
+            },
+            structures::square::SqPos::{self, *},
+        },
+    };
 
     use super::*;
+    use crate::engine::search::{history::MAX_HISTORY, transposition_table::DEFAULT_HASH_MB};
+
+    fn search_score(fen: &str, depth: u8) -> Score {
+        let mut game = Game::read_fen(fen);
+        clear_search(&mut game);
+
+        let mut score = 0;
+        for d in 1..=depth {
+            score = alpha_beta(-INF, INF, d, &mut game, true);
+        }
+        score
+    }
+
+    #[test]
+    fn test_mate_in_3() {
+        // The shortest mate is 1. Qg6, the queen can't be taken without allowing Nxg6#
+        assert_eq!(search_score(FEN_MATE_IN_3, 8).mate_moves(), Some(2));
+    }
+
+    #[test]
+    fn test_mate_in_4() {
+        // 1. Qg7+ Kxg7 2. Nf5+ is already a mate in 3
+        assert_eq!(search_score(FEN_MATE_IN_4, 10).mate_moves(), Some(3));
+    }
+
+    #[test]
+    fn test_qsearch_in_check_has_no_stand_pat() {
+        // Fool's mate, white has no evasion
+        let mut game =
+            Game::read_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3");
+        clear_search(&mut game);
+        assert_eq!(quiescence_search(-INF, INF, false, &mut game), mated_in(0));
+    }
+
+    #[test]
+    fn test_qsearch_quiet_checks() {
+        // The back rank mate Ra8# is a quiet move, only found with the checks at the first ply
+        let fen = "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1";
+        let mut game = Game::read_fen(fen);
+        clear_search(&mut game);
+        assert_eq!(quiescence_search(-INF, INF, true, &mut game), mate_in(1));
+
+        let mut game = Game::read_fen(fen);
+        clear_search(&mut game);
+        assert!(quiescence_search(-INF, INF, false, &mut game).mate_moves().is_none());
+    }
+
+    #[test]
+    fn test_qsearch_delta_pruning() {
+        // A queen down, winning a pawn back can't bring the score anywhere near alpha
+        let mut game = Game::read_fen("q3k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1");
+        clear_search(&mut game);
+        assert_eq!(quiescence_search(-100, -99, false, &mut game), -100);
+        assert_eq!(game.info.nodes, 1);
+    }
+
+    #[test]
+    fn test_qsearch_stores_tt() {
+        let mut game = Game::read_fen("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1");
+        clear_search(&mut game);
+        let score = quiescence_search(-INF, INF, false, &mut game);
+        assert!(score > 0);
+
+        let entry = game.info.shared.tt.get(game.key).unwrap();
+        assert_eq!((entry.depth, entry.category), (0, Bound::Exact));
+        let rev = entry.rev(&game).unwrap();
+        assert_eq!((rev.from, rev.to), (D2 as u8, D5 as u8));
+
+        // The second probe is answered by the table
+        game.info.nodes = 0;
+        assert_eq!(quiescence_search(-INF, INF, false, &mut game), score);
+        assert_eq!(game.info.nodes, 1);
+    }
+
+    #[test]
+    fn test_lazy_smp_finds_mate() {
+        let mut game = Game::read_fen(FEN_MATE_IN_3);
+        game.info.depth = Some(8);
+
+        let tt = Arc::new(TTTable::init(DEFAULT_HASH_MB));
+        let mv = lazy_smp(&mut game, 3, &tt, &Arc::default(), &Arc::default()).unwrap();
+        assert_eq!((mv.from, mv.to), (G3 as u8, G6 as u8));
+        assert!(game.info.shared.total_nodes() > game.info.nodes);
+    }
+
+    #[test]
+    fn test_tt_is_kept_between_searches() {
+        let tt = Arc::new(TTTable::init(DEFAULT_HASH_MB));
+        let mut game = Game::read_fen(FEN_MATE_IN_3);
+        game.info.depth = Some(6);
+
+        let cancel = Arc::new(AtomicBool::new(false));
+        lazy_smp(&mut game.clone(), 1, &tt, &cancel, &Arc::default());
+        let entry = tt.get(game.key).unwrap();
+        assert_eq!(entry.age, 1);
+
+        // The second search starts from the entries of the first one, and still finds a move
+        assert!(lazy_smp(&mut game, 1, &tt, &cancel, &Arc::default()).is_some());
+        assert_eq!(tt.get(game.key).map(|e| e.age), Some(2));
+        assert!(Arc::ptr_eq(&game.info.shared.tt, &tt));
+    }
+
+    #[test]
+    fn test_cancelled_search_returns_a_move() {
+        let tt = Arc::new(TTTable::init(1));
+        let cancel = Arc::new(AtomicBool::new(false));
+        let mut game = Game::read_fen(FEN_POS_TWO);
+        game.info.depth = Some(64);
+        let (_, legal) = gen_legal_moves(&game);
+
+        // The search thread takes the time it returns, so joining it doesn't count
+        let handle = {
+            let (tt, cancel) = (Arc::clone(&tt), Arc::clone(&cancel));
+            thread::spawn(move || {
+                let mv = lazy_smp(&mut game, 2, &tt, &cancel, &Arc::default());
+                (mv, Instant::now())
+            })
+        };
+        thread::sleep(Duration::from_millis(200));
+
+        // A depth 64 search only comes back this soon because it was cancelled
+        let cancelled = Instant::now();
+        cancel.store(true, Ordering::Relaxed);
+        let (mv, returned) = handle.join().unwrap();
+        assert!(returned.duration_since(cancelled) < Duration::from_millis(50));
+        assert!(legal.contains(&mv.unwrap()));
+
+        // Cancelled before it started, it still has a move to play
+        let mut game = Game::read_fen(FEN_MATE_IN_3);
+        assert!(lazy_smp(&mut game, 1, &tt, &cancel, &Arc::default()).is_some());
+    }
+
+    #[test]
+    fn test_ponderhit_turns_into_timed_search() {
+        let ponder = Arc::new(AtomicBool::new(true));
+        let mut game = Game::read_fen(FEN_POS_TWO);
+        game.info.depth = Some(64);
+        game.info.time = Some(TimeManager::fixed(50, 0));
+
+        let handle = {
+            let ponder = Arc::clone(&ponder);
+            thread::spawn(move || {
+                let tt = Arc::new(TTTable::init(1));
+                let mv = lazy_smp(&mut game, 1, &tt, &Arc::default(), &ponder).unwrap();
+                (mv, Instant::now(), ponder_move(&mut game, &mv), game)
+            })
+        };
+
+        // The time limit passes while pondering, but the clock doesn't run until ponderhit
+        thread::sleep(Duration::from_millis(300));
+        let ponderhit = Instant::now();
+        ponder.store(false, Ordering::Relaxed);
+
+        let (mv, finished, reply, mut game) = handle.join().unwrap();
+        assert!(finished >= ponderhit);
+        assert_eq!(Some(mv), game.info.root_moves.first().map(|rm| rm.rev));
+        game.make_legal_move(&mv, &PositionIrr::init_with_game(&game));
+        assert!(gen_legal_moves(&game).1.contains(&reply.unwrap()));
+    }
+
+    #[test]
+    fn test_multi_pv() {
+        let mut game = Game::read_fen(FEN_POS_TWO);
+        game.info.depth = Some(5);
+        game.info.multi_pv = 3;
+        let cancel = Arc::new(AtomicBool::new(false));
+        let mv = lazy_smp(&mut game, 1, &Arc::new(TTTable::init(1)), &cancel, &Arc::default());
+
+        let lines = &game.info.root_moves[..3];
+        assert_eq!(game.info.root_moves.len(), 48);
+        assert!(lines.windows(2).all(|w| w[0].score >= w[1].score && w[0].rev != w[1].rev));
+        assert!(lines.iter().all(|rm| rm.pv[0] == rm.rev && rm.score > -INF));
+        // Each line kept the score of depth 4 to open its window, not the -INF the search of the
+        // lines before it left
+        assert!(lines.iter().all(|rm| rm.prev_score > -INF));
+        assert_eq!(mv, Some(lines[0].rev));
+    }
+
+    fn limited_search(fen: &str, f: impl Fn(&mut Game)) -> (Option<PositionRev>, Game) {
+        let mut game = Game::read_fen(fen);
+        game.info.depth = Some(30);
+        f(&mut game);
+        let mv =
+            lazy_smp(&mut game, 1, &Arc::new(TTTable::init(1)), &Arc::default(), &Arc::default());
+        (mv, game)
+    }
+
+    #[test]
+    fn test_pv_is_complete() {
+        let (mv, game) = limited_search(FEN_POS_TWO, |game| game.info.depth = Some(7));
+        let pv = &game.info.root_moves[0].pv;
+        assert_eq!(pv.first(), mv.as_ref());
+        assert!(pv.len() >= 7);
+
+        // Every move of the line can be played in turn
+        let mut game = Game::read_fen(FEN_POS_TWO);
+        for rev in pv {
+            assert!(gen_legal_moves(&game).1.contains(rev));
+            game.make_legal_move(rev, &PositionIrr::init_with_game(&game));
+        }
+    }
+
+    #[test]
+    fn test_info_line() {
+        let (_, game) = limited_search(FEN_POS_TWO, |game| game.info.depth = Some(6));
+        let rm = &game.info.root_moves[0];
+        let line = info_line(&game, 1, rm.score, Bound::Lower, &rm.pv);
+
+        let field = |name: &str| -> usize {
+            let mut words = line.split_whitespace().skip_while(|word| *word != name);
+            words.nth(1).and_then(|value| value.parse().ok()).unwrap()
+        };
+        assert_eq!(field("depth"), 6);
+        assert!(field("seldepth") > 6);
+        assert_eq!(field("nodes"), game.info.shared.total_nodes());
+        assert!(field("nps") > 0);
+        assert!(
+            line.contains(" lowerbound ") && line.contains(" hashfull ") && line.contains(" time ")
+        );
+        assert!(line.ends_with(&get_move_list(&rm.pv, &game)));
+        assert!(!line.contains(" wdl "));
+
+        let mut game = game.clone();
+        game.info.show_wdl = true;
+        let line = info_line(&game, 1, rm.score, Bound::Lower, &rm.pv);
+        let (win, draw, loss) = rm.score.to_wdl();
+        assert!(line.contains(&format!(" lowerbound wdl {} {} {} nodes ", win, draw, loss)));
+    }
+
+    #[test]
+    fn test_info_rate_limit() {
+        let mut game = Game::initialize();
+        clear_search(&mut game);
+        assert!(info_due(&mut game.info.last_info, INFO_INTERVAL));
+        assert!(!info_due(&mut game.info.last_info, INFO_INTERVAL));
+        assert!(info_due(&mut game.info.last_bound_info, INFO_INTERVAL));
+        assert!(info_due(&mut game.info.last_currmove, CURRMOVE_INTERVAL));
+
+        game.info.last_info = Some(Instant::now() - INFO_INTERVAL);
+        assert!(info_due(&mut game.info.last_info, INFO_INTERVAL));
+    }
+
+    #[test]
+    fn test_node_limit_is_reproducible() {
+        let limit = |game: &mut Game| game.info.node_limit = Some(50_000);
+        let (mv, game) = limited_search(FEN_POS_TWO, limit);
+        let (mv_again, game_again) = limited_search(FEN_POS_TWO, limit);
+
+        assert_eq!(mv, mv_again);
+        assert_eq!(game.info.nodes, game_again.info.nodes);
+        // Only the nodes entered before a move loop checks the budget again can go past it
+        assert!((50_000..50_000 + MAX_PLY).contains(&game.info.nodes));
+    }
+
+    #[test]
+    fn test_node_limit_is_shared_by_threads() {
+        let mut game = Game::read_fen(FEN_POS_TWO);
+        game.info.depth = Some(30);
+        game.info.node_limit = Some(200_000);
+        let tt = Arc::new(TTTable::init(1));
+        assert!(lazy_smp(&mut game, 4, &tt, &Arc::default(), &Arc::default()).is_some());
+
+        // The main thread only stops once its count and the published ones reach the budget.
+        // How far the others got past their last checkup depends on the scheduler, but none of
+        // them searches past the budget on its own.
+        let total = game.info.shared.total_nodes();
+        assert!(game.info.nodes + game.info.other_nodes >= 200_000);
+        assert!((200_000..4 * (200_000 + MAX_PLY)).contains(&total));
+    }
+
+    #[test]
+    fn test_mate_limit() {
+        let (mv, game) = limited_search(FEN_MATE_IN_3, |game| game.info.mate = Some(2));
+        assert_eq!(mv.map(|mv| (mv.from, mv.to)), Some((G3 as u8, G6 as u8)));
+        assert_eq!(game.info.root_moves[0].score.mate_moves(), Some(2));
+        assert!(game.info.curr_depth < 30);
+    }
+
+    #[test]
+    fn test_search_moves() {
+        let (_, legal) = gen_legal_moves(&Game::read_fen(FEN_MATE_IN_3));
+        let only = legal.iter().filter(|mv| (mv.from, mv.to) != (G3 as u8, G6 as u8));
+        let search_moves: Vec<PositionRev> = only.take(2).copied().collect();
+
+        let (mv, game) = limited_search(FEN_MATE_IN_3, |game| {
+            game.info.depth = Some(4);
+            game.info.search_moves = search_moves.clone();
+        });
+        assert!(search_moves.contains(&mv.unwrap()));
+        assert_eq!(game.info.root_moves.len(), 2);
+    }
+
+    #[test]
+    fn test_widen_window() {
+        // Fail low moves alpha below the score, fail high moves beta above it
+        assert_eq!(widen_window(-30, 30, -50, 60), (-110, 30));
+        assert_eq!(widen_window(-30, 30, 45, 60), (-30, 105));
+        assert_eq!(widen_window(-30, 30, -INF + 10, 60), (-INF, 30));
+    }
+
+    #[test]
+    fn test_aspiration_keeps_exact_scores() {
+        // The mate is found at a depth where the window is already narrow, so it has to be
+        // re-searched until the score is exact
+        let mut game = Game::read_fen(FEN_MATE_IN_5);
+        game.info.depth = Some(12);
+        lazy_smp(&mut game, 1, &Arc::new(TTTable::init(1)), &Arc::default(), &Arc::default());
+        assert_eq!(game.info.root_moves[0].score.mate_moves(), Some(5));
+    }
+
+    #[test]
+    fn test_mate_in_5() {
+        assert_eq!(search_score(FEN_MATE_IN_5, 12).mate_moves(), Some(5));
+    }
+
+    #[test]
+    fn test_check_extension_finds_deeper_mate() {
+        // The mate takes 9 plies, the checks on the way are extended
+        assert_eq!(search_score(FEN_MATE_IN_5, 5).mate_moves(), Some(5));
+    }
+
+    #[test]
+    fn test_singular_extension() {
+        let e = TTEntry::init(0, 0, 0, 0, 8, Bound::Lower, 0);
+        let find = |game: &Game, from: SqPos, to: SqPos| {
+            let (from, to) = (from as u8, to as u8);
+            gen_legal_moves(game).1.into_iter().find(|mv| (mv.from, mv.to) == (from, to)).unwrap()
+        };
+
+        // Taking back the rook is the only move that doesn't lose it
+        let mut game = Game::read_fen("4k3/pppp4/8/8/8/8/3r1PPP/4K3 w - - 0 1");
+        clear_search(&mut game);
+        game.ply = 1;
+        let rev = find(&game, E1, D2);
+        assert!(is_singular(&mut game, &rev, e, 8));
+        assert_eq!(game.s_excluded[1], None);
+
+        // Too shallow to be trusted, and an upper bound says nothing about the other moves
+        assert!(!is_singular(&mut game, &rev, TTEntry { depth: 2, ..e }, 8));
+        assert!(!is_singular(&mut game, &rev, TTEntry { category: Bound::Upper, ..e }, 8));
+
+        // In the opening plenty of moves keep the balance
+        let mut game = Game::initialize();
+        clear_search(&mut game);
+        game.ply = 1;
+        let rev = find(&game, E2, E4);
+        assert!(!is_singular(&mut game, &rev, e, 8));
+    }
+
+    #[test]
+    fn test_lmr_reduction_bounds() {
+        let game = Game::initialize();
+        for depth in LMR_MIN_DEPTH..64 {
+            for mv_num in 1..100 {
+                let r = lmr_reduction(depth, mv_num, false, false, 0, &game);
+                assert!(depth - 1 - r >= 1);
+                assert!(r >= lmr_reduction(depth, mv_num, true, true, MAX_HISTORY * 3, &game));
+                assert!(r <= lmr_reduction(depth, mv_num, false, false, -MAX_HISTORY * 3, &game));
+            }
+        }
+        assert!(LMR_TABLE[20][40] > LMR_TABLE[3][4]);
+    }
 
     // NOTE: Uncomment In Cargo.toml the pprof to see the performance.
     //     #[test]
@@ -1,32 +1,166 @@
-use std::time::Duration;
+use std::{sync::atomic::Ordering, time::Duration};
 
-use crate::engine::game::Game;
+use crate::engine::{game::Game, search::score::Score};
 
-pub fn set_time_limit(mv_played: usize, time: usize, inc: usize) -> Duration {
-    let nMoves = mv_played.min(10);
-    let factor = 2 - nMoves / 10;
-    let target = (time + inc) / (60 - mv_played);
-    let limit: u64 = (factor * target) as u64;
-    println!("{:?} {:?} {:?} {:?}", nMoves, factor, target, limit);
-    Duration::from_millis(limit)
+// Moves the remaining time is spread over when the GUI doesn't send movestogo
+const DEFAULT_MOVES_TOGO: u64 = 30;
+const MAX_MOVES_TOGO: u64 = 50;
+// The maximum is this many times the optimum, but never more than the fraction of the clock
+const MAX_OPTIMUM_RATIO: u32 = 5;
+const MAX_CLOCK_FRACTION: f64 = 0.8;
+
+/// Time allowed for one move. No new iteration is started after the optimum (scaled by how
+/// settled the search is), and the search is aborted at the maximum.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimeManager {
+    pub optimum: Duration,
+    pub maximum: Duration,
 }
 
-pub fn safe_to_start_next_iter(game: &Game) -> bool {
+impl TimeManager {
+    /// Clock of the side to move (wtime/btime, winc/binc, movestogo), all in milliseconds.
+    /// `overhead` is kept in reserve for the communication with the GUI.
+    pub fn init(time: u64, inc: u64, moves_togo: Option<u64>, overhead: u64) -> Self {
+        let time = time.saturating_sub(overhead).max(1);
+        let moves_togo = moves_togo.unwrap_or(DEFAULT_MOVES_TOGO).clamp(1, MAX_MOVES_TOGO);
+
+        let cap = (time as f64 * MAX_CLOCK_FRACTION) as u64;
+        let optimum = (time / moves_togo + inc * 3 / 4).min(cap).max(1);
+        let maximum = (optimum * MAX_OPTIMUM_RATIO as u64).min(cap).max(optimum);
+
+        Self { optimum: Duration::from_millis(optimum), maximum: Duration::from_millis(maximum) }
+    }
+
+    /// go movetime, the whole time is used.
+    pub fn fixed(time: u64, overhead: u64) -> Self {
+        let time = Duration::from_millis(time.saturating_sub(overhead).max(1));
+        Self { optimum: time, maximum: time }
+    }
+
+    /// The optimum scaled by `scale`, a fixed move time has no soft limit.
+    pub fn soft_limit(&self, scale: f64) -> Duration {
+        match self.optimum == self.maximum {
+            true => self.maximum,
+            false => self.optimum.mul_f64(scale).min(self.maximum),
+        }
+    }
+
+    /// How much of the optimum to use. More when the best move keeps changing (`best_changes`
+    /// decays every iteration) or the score dropped since the last iteration, less when the
+    /// best move took nearly all the nodes at the root (`best_effort`, 0 to 1).
+    pub fn scale(best_changes: f64, score_drop: Score, best_effort: f64) -> f64 {
+        let instability = 1.0 + best_changes.min(4.0) * 0.3;
+        let falling = 1.0 + score_drop.clamp(0, 100) as f64 / 200.0;
+        let dominance = match best_effort {
+            e if e >= 0.95 => 0.4,
+            e if e >= 0.85 => 0.7,
+            _ => 1.0,
+        };
+        instability * falling * dominance
+    }
+}
+
+pub fn safe_to_start_next_iter(game: &Game, scale: f64) -> bool {
     if time_over(game) {
         return false;
     }
+    if pondering(game) {
+        return true;
+    }
 
-    let elapsed = game.info.start_time.elapsed();
-    let total_time = game.info.time_limit.unwrap_or(Duration::from_millis(u64::MAX));
-    let remaining_time = total_time - elapsed;
-    let threshold = remaining_time.mul_f32(0.4); // 40% of remaining time
-
-    // Allow another iteration only if less than 40% of remaining time has been used
-    elapsed < (total_time - threshold)
+    match game.info.time {
+        Some(tm) => game.info.start_time.elapsed() < tm.soft_limit(scale),
+        None => true,
+    }
 }
 
 pub fn time_over(game: &Game) -> bool {
-    game.info.start_time.elapsed()
-        >= game.info.time_limit.unwrap_or(Duration::from_millis(u64::MAX))
+    (!pondering(game)
+        && game.info.time.is_some_and(|tm| game.info.start_time.elapsed() >= tm.maximum))
         || game.info.stopped
+        || game.info.shared.stop.load(Ordering::Relaxed)
+        || game.info.shared.cancel.load(Ordering::Relaxed)
+        || nodes_over(game)
+}
+
+/// The node budget of `go nodes`: the nodes of this thread so far, plus the other threads as
+/// they were at the last checkup. The others are at most 2048 nodes further each, so with
+/// several threads the budget can be overshot by that much.
+pub fn nodes_over(game: &Game) -> bool {
+    game.info.node_limit.is_some_and(|limit| game.info.nodes + game.info.other_nodes >= limit)
+}
+
+/// While pondering the clock doesn't run, the time spent still counts after ponderhit.
+pub fn pondering(game: &Game) -> bool {
+    game.info.shared.ponder.load(Ordering::Relaxed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(tm: TimeManager) -> (u128, u128) {
+        (tm.optimum.as_millis(), tm.maximum.as_millis())
+    }
+
+    #[test]
+    fn test_sudden_death() {
+        // 1 minute for the rest of the game
+        let (optimum, maximum) = ms(TimeManager::init(60_000, 0, None, 10));
+        assert_eq!(optimum, 59_990 / 30);
+        assert_eq!(maximum, optimum * 5);
+
+        // Almost out of time, still a limit that leaves something on the clock
+        let (optimum, maximum) = ms(TimeManager::init(50, 0, None, 10));
+        assert!(optimum >= 1 && optimum <= maximum && maximum <= 40);
+        assert_eq!(ms(TimeManager::init(0, 0, None, 10)), (1, 1));
+    }
+
+    #[test]
+    fn test_increment() {
+        let (optimum, maximum) = ms(TimeManager::init(10_000, 1_000, None, 0));
+        assert_eq!(optimum, 10_000 / 30 + 750);
+        assert!(maximum > optimum && maximum <= 8_000);
+
+        // Living off the increment, the clock itself is never used up
+        let (optimum, maximum) = ms(TimeManager::init(500, 2_000, None, 0));
+        assert!(optimum <= 400 && maximum <= 400);
+    }
+
+    #[test]
+    fn test_repeating_time_control() {
+        // 40 moves in 5 minutes
+        let (optimum, _) = ms(TimeManager::init(300_000, 0, Some(40), 0));
+        assert_eq!(optimum, 300_000 / 40);
+
+        // The last move before the time control can use most of the clock, but not all of it
+        let (optimum, maximum) = ms(TimeManager::init(10_000, 0, Some(1), 0));
+        assert_eq!((optimum, maximum), (8_000, 8_000));
+
+        // A movestogo beyond 50 is capped at 50
+        assert_eq!(
+            TimeManager::init(100_000, 0, Some(500), 0),
+            TimeManager::init(100_000, 0, Some(50), 0)
+        );
+    }
+
+    #[test]
+    fn test_fixed_move_time() {
+        let tm = TimeManager::fixed(1_000, 10);
+        assert_eq!(ms(tm), (990, 990));
+        assert_eq!(tm.soft_limit(0.4), tm.maximum);
+    }
+
+    #[test]
+    fn test_scale() {
+        let settled = TimeManager::scale(0.0, 0, 0.5);
+        assert_eq!(settled, 1.0);
+        assert!(TimeManager::scale(2.0, 0, 0.5) > settled);
+        assert!(TimeManager::scale(0.0, 60, 0.5) > settled);
+        assert!(TimeManager::scale(0.0, 0, 0.97) < settled);
+
+        let tm = TimeManager::init(60_000, 0, None, 0);
+        assert_eq!(tm.soft_limit(100.0), tm.maximum);
+        assert!(tm.soft_limit(0.4) < tm.optimum);
+    }
 }
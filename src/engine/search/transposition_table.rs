@@ -1,10 +1,27 @@
+use std::sync::atomic::{AtomicU32, AtomicU64, AtomicU8, Ordering};
+
 use crate::engine::{
     game::{self, Game},
-    move_generation::{make_move::GameMoveTrait, mv_gen::move_exists},
-    shared::structures::internal_move::{PositionIrr, PositionRev},
+    search::score::{Score, ScoreTrait, INF},
+    shared::structures::{
+        internal_move::{Flag, PositionRev},
+        piece::{Piece, PieceTrait, BISHOP, KNIGHT, QUEEN, ROOK},
+    },
 };
 
-const MAX_TT_ENTRIES: usize = 2440211;
+/// Hash size in MB used until the GUI sets one.
+pub const DEFAULT_HASH_MB: usize = 16;
+pub const MAX_HASH_MB: usize = 65536;
+
+// NOTE: Slots 0..DEPTH_SLOTS keep the deepest entries, the last slot takes everything else.
+const BUCKET_SIZE: usize = 5;
+const DEPTH_SLOTS: usize = BUCKET_SIZE - 1;
+// NOTE: The age is stored in 6 bits, so it wraps around after 64 searches.
+const AGE_MASK: u8 = 63;
+// A result of the same position replaces a deeper entry only if it is at most this much shallower
+const SAME_KEY_DEPTH_MARGIN: u8 = 3;
+// Entries looked at to estimate how full the table is
+const HASHFULL_SAMPLE: usize = 1000;
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Bound {
@@ -13,105 +30,434 @@ pub enum Bound {
     Upper,
 }
 
-// NOTE: 64 + 32 + 16 + 8 + 8 = 128 BITS = 16 Bytes
-// NOTE: 1Mb = 1000000 Bytes = 166,666 Entries
-// NOTE: Currently Around 15Mb
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct TTEntry {
     pub key: u64,
-    pub rev: PositionRev,
+    pub mv: u16,
     pub score: i16,
+    pub eval: i16,
     pub depth: u8,
     pub category: Bound,
+    pub age: u8,
 }
 
 impl TTEntry {
-    pub fn init(key: u64, rev: PositionRev, score: i16, depth: u8, category: Bound) -> Self {
-        Self { key, rev, score, depth, category }
+    pub fn init(
+        key: u64,
+        mv: u16,
+        score: i16,
+        eval: i16,
+        depth: u8,
+        category: Bound,
+        age: u8,
+    ) -> Self {
+        Self { key, mv, score, eval, depth, category, age }
+    }
+
+    /// The stored move is only 16 bits, the rest of it is read from the position. It can still
+    /// be illegal (another position with the same key fragment), so it has to be verified.
+    #[inline(always)]
+    pub fn rev(&self, game: &Game) -> Option<PositionRev> {
+        unpack_move(self.mv, game)
+    }
+}
+
+/// A search result as the search hands it to `set`, the score is still relative to the root.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TTData {
+    pub rev: PositionRev,
+    pub score: Score,
+    pub eval: Score,
+    pub depth: u8,
+    pub bound: Bound,
+}
+
+// NOTE: Each entry is a 32 bit check and 64 bits of data. The check is the low half of the key
+// XOR-ed with both halves of the data, so a write torn by another thread (or an entry of
+// another position in the same bucket) doesn't verify and is read as a miss.
+#[repr(align(64))]
+#[derive(Debug, Default)]
+struct TTBucket {
+    check: [AtomicU32; BUCKET_SIZE],
+    data: [AtomicU64; BUCKET_SIZE],
+}
+
+const _: () = assert!(std::mem::size_of::<TTBucket>() == 64);
+
+impl TTBucket {
+    #[inline(always)]
+    fn read(&self, slot: usize, key: u64) -> Option<u64> {
+        let data = self.data[slot].load(Ordering::Relaxed);
+        match data != 0 && self.check[slot].load(Ordering::Relaxed) == check(key, data) {
+            true => Some(data),
+            false => None,
+        }
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[inline(always)]
+fn check(key: u64, data: u64) -> u32 {
+    key as u32 ^ data as u32 ^ (data >> 32) as u32
+}
+
+/// Transposition table shared by every search thread. It is lock-free: slots are written
+/// and read with relaxed atomics and verified on read instead. It outlives a single search,
+/// the age tells entries of the current search apart from the stale ones of earlier searches.
+#[derive(Debug)]
 pub struct TTTable {
-    pub table: Vec<Option<TTEntry>>,
-    pub lookups: u64,
-    pub inserts: u64,
-    pub hits: u64,
-    pub collisions: u64,
+    table: Vec<TTBucket>,
+    age: AtomicU8,
 }
 
 impl TTTable {
-    pub fn init() -> Self {
-        Self { table: vec![None; MAX_TT_ENTRIES], lookups: 0, inserts: 0, hits: 0, collisions: 0 }
+    /// The number of buckets is the largest power of two that fits in `mb` megabytes.
+    pub fn init(mb: usize) -> Self {
+        let bytes = mb.clamp(1, MAX_HASH_MB) << 20;
+        Self::with_buckets(1 << (bytes / size_of::<TTBucket>()).ilog2())
+    }
+
+    /// A table of `buckets` cache lines. A single bucket is the placeholder a `Game` holds until
+    /// a search hands it the table of the caller.
+    pub fn with_buckets(buckets: usize) -> Self {
+        let buckets = buckets.max(1);
+        Self { table: (0..buckets).map(|_| TTBucket::default()).collect(), age: AtomicU8::new(0) }
+    }
+
+    /// Called once at the start of every search, so the entries written until now become stale.
+    pub fn new_search(&self) {
+        let age = self.age();
+        self.age.store((age + 1) & AGE_MASK, Ordering::Relaxed);
+    }
+
+    #[inline(always)]
+    pub fn age(&self) -> u8 {
+        self.age.load(Ordering::Relaxed)
+    }
+
+    /// Multiply-high: maps the key onto the buckets using its high bits, the low bits are
+    /// left for the check.
+    #[inline(always)]
+    fn idx(&self, key: u64) -> usize {
+        ((key as u128 * self.table.len() as u128) >> 64) as usize
     }
 
-    pub fn idx(key: u64) -> usize {
-        return (key % MAX_TT_ENTRIES as u64) as usize;
+    /// Stores a search result, `ply` is needed to store mate scores relative to this node.
+    pub fn set(&self, key: u64, ply: usize, data: TTData) {
+        let bucket = &self.table[self.idx(key)];
+        let Some(slot) = self.replace_slot(bucket, key, data.depth, data.bound) else {
+            return;
+        };
+
+        let entry = TTEntry::init(
+            key,
+            pack_move(&data.rev),
+            data.score.to_tt(ply),
+            data.eval.clamp(-INF, INF) as i16,
+            data.depth,
+            data.bound,
+            self.age(),
+        );
+        let packed = pack_entry(&entry);
+        bucket.check[slot].store(check(key, packed), Ordering::Relaxed);
+        bucket.data[slot].store(packed, Ordering::Relaxed);
     }
 
-    pub fn set(&mut self, key: u64, rev: PositionRev, score: i16, depth: u8, category: Bound) {
-        self.table[Self::idx(key)] = Some(TTEntry::init(key, rev, score, depth, category));
+    /// The entry of the same position is only overwritten by an exact result, a result that is
+    /// nearly as deep or when it is stale, so a quiescence search doesn't wipe out a deep entry
+    /// (`None`: the result is dropped). Otherwise the result replaces the worst depth-preferred
+    /// entry (empty, then stale, then the shallowest one) if it is at least as deep, and goes
+    /// into the always-replace slot if it isn't.
+    #[inline(always)]
+    fn replace_slot(&self, bucket: &TTBucket, key: u64, depth: u8, bound: Bound) -> Option<usize> {
+        let age = self.age();
+        if let Some(slot) = (0..BUCKET_SIZE).find(|&slot| bucket.read(slot, key).is_some()) {
+            let data = bucket.data[slot].load(Ordering::Relaxed);
+            let (old_depth, old_age) = ((data >> 48) as u8, (data >> 58) as u8);
+            return match bound == Bound::Exact
+                || depth.saturating_add(SAME_KEY_DEPTH_MARGIN) >= old_depth
+                || old_age != age
+            {
+                true => Some(slot),
+                false => None,
+            };
+        }
+
+        let worth = |slot: usize| -> isize {
+            let data = bucket.data[slot].load(Ordering::Relaxed);
+            match (data, ((data >> 58) as u8) == age) {
+                (0, _) => -2,
+                (_, false) => -1,
+                (_, true) => (data >> 48) as u8 as isize,
+            }
+        };
+
+        let slot = (0..DEPTH_SLOTS).min_by_key(|&slot| worth(slot)).unwrap_or(DEPTH_SLOTS);
+        match depth as isize >= worth(slot) {
+            true => Some(slot),
+            false => Some(DEPTH_SLOTS),
+        }
     }
 
-    pub fn probe(&self, key: u64, depth: u8, mut alpha: i16, mut beta: i16) -> Option<i16> {
-        let idx = Self::idx(key);
-        if let Some(entry) = self.table.get(idx) {
-            if let Some(e) = *entry {
-                if e.key == key && e.depth >= depth {
-                    match e.category {
-                        Bound::Lower => alpha = alpha.max(e.score),
-                        Bound::Exact => return Some(e.score),
-                        Bound::Upper => beta = beta.min(e.score),
-                    }
-                    if alpha >= beta {
-                        return Some(e.score);
-                    }
-                }
+    pub fn probe(
+        &self,
+        key: u64,
+        depth: u8,
+        ply: usize,
+        mut alpha: Score,
+        mut beta: Score,
+    ) -> Option<Score> {
+        let e = self.get(key)?;
+        if e.depth >= depth {
+            let score = Score::from_tt(e.score, ply);
+            match e.category {
+                Bound::Lower => alpha = alpha.max(score),
+                Bound::Exact => return Some(score),
+                Bound::Upper => beta = beta.min(score),
+            }
+            if alpha >= beta {
+                return Some(score);
             }
         }
 
-        return None;
+        None
     }
 
     pub fn get(&self, key: u64) -> Option<TTEntry> {
-        let idx = Self::idx(key);
-        if let Some(entry) = self.table.get(idx) {
-            if let Some(e) = *entry {
-                if e.key == key {
-                    return Some(e);
-                }
+        let bucket = &self.table[self.idx(key)];
+        (0..BUCKET_SIZE).find_map(|slot| bucket.read(slot, key)).map(|data| unpack_entry(key, data))
+    }
+
+    /// Permille of the sampled entries that were written by the current search (UCI hashfull).
+    pub fn hashfull(&self) -> usize {
+        let age = self.age();
+        let buckets = (HASHFULL_SAMPLE / BUCKET_SIZE).min(self.table.len());
+        let used = self.table[..buckets]
+            .iter()
+            .flat_map(|bucket| &bucket.data)
+            .map(|data| data.load(Ordering::Relaxed))
+            .filter(|&data| data != 0 && (data >> 58) as u8 == age)
+            .count();
+
+        used * 1000 / (buckets * BUCKET_SIZE)
+    }
+
+    pub fn clear(&self) {
+        for bucket in &self.table {
+            for slot in 0..BUCKET_SIZE {
+                bucket.check[slot].store(0, Ordering::Relaxed);
+                bucket.data[slot].store(0, Ordering::Relaxed);
             }
         }
-
-        return None;
+        self.age.store(0, Ordering::Relaxed);
     }
+}
 
-    pub fn clear(&mut self) {
-        self.table.fill(None);
+/// Two games are only equal if they share the same table, the content is never compared.
+impl PartialEq for TTTable {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
     }
 }
 
-pub fn get_line(game: &mut Game, mut pos_key: u64) -> Vec<PositionRev> {
-    let mut line: Vec<PositionRev> = Vec::with_capacity(64); // TODO: Max Depth Add as a constant
+impl Eq for TTTable {}
+
+// NOTE: Data layout: move 0..16 | score 16..32 | eval 32..48 | depth 48..56 | bound 56..58 |
+// NOTE: age 58..64. The bound is stored as 1..=3, so a written entry is never 0.
+#[inline(always)]
+fn pack_entry(e: &TTEntry) -> u64 {
+    let bound = match e.category {
+        Bound::Lower => 1,
+        Bound::Exact => 2,
+        Bound::Upper => 3,
+    };
+    e.mv as u64
+        | (e.score as u16 as u64) << 16
+        | (e.eval as u16 as u64) << 32
+        | (e.depth as u64) << 48
+        | bound << 56
+        | (e.age as u64) << 58
+}
+
+#[inline(always)]
+fn unpack_entry(key: u64, data: u64) -> TTEntry {
+    let category = match (data >> 56) & 3 {
+        1 => Bound::Lower,
+        2 => Bound::Exact,
+        _ => Bound::Upper,
+    };
+    let score = (data >> 16) as u16 as i16;
+    let eval = (data >> 32) as u16 as i16;
+    let depth = (data >> 48) as u8;
+    let age = (data >> 58) as u8;
+    TTEntry::init(key, data as u16, score, eval, depth, category, age)
+}
+
+// NOTE: Move layout: from 0..6 | to 6..12 | kind 12..16
+// NOTE: Kind: quiet 0, king castle 1, queen castle 2, capture 3, ep 4, promotion 5..=8 (N, B, R, Q)
+const PROMO_KINDS: [Piece; 4] = [KNIGHT, BISHOP, ROOK, QUEEN];
+
+#[inline(always)]
+fn pack_move(rev: &PositionRev) -> u16 {
+    let kind = match rev.flag {
+        Flag::Quiet => 0,
+        Flag::KingCastle => 1,
+        Flag::QueenCastle => 2,
+        Flag::Capture(_) => 3,
+        Flag::EP => 4,
+        Flag::Promotion(promo, _) => {
+            5 + PROMO_KINDS.iter().position(|&kind| kind == promo.kind()).unwrap_or(3) as u16
+        }
+    };
+    rev.from as u16 | (rev.to as u16) << 6 | kind << 12
+}
+
+/// The moving and the captured piece are taken from the board.
+#[inline(always)]
+fn unpack_move(mv: u16, game: &Game) -> Option<PositionRev> {
+    let (from, to) = ((mv & 63) as u8, ((mv >> 6) & 63) as u8);
+    let piece = game.squares[from as usize]?;
+    let captured = game.squares[to as usize];
 
-    while let Some(mv) = game.tt.get(pos_key) {
-        if line.len() >= 64 {
-            break;
+    let flag = match mv >> 12 {
+        0 => Flag::Quiet,
+        1 => Flag::KingCastle,
+        2 => Flag::QueenCastle,
+        3 => Flag::Capture(captured?),
+        4 => Flag::EP,
+        kind @ 5..=8 => Flag::Promotion(PROMO_KINDS[kind as usize - 5] | piece.color(), captured),
+        _ => return None,
+    };
+    Some(PositionRev::init(from, to, piece, flag))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{
+        fen::fen::FenTrait,
+        move_generation::legal_mv_gen::gen_legal_moves,
+        shared::helper_func::const_utility::{FEN_POS_FIVE, FEN_POS_FOUR, FEN_POS_TWO},
+    };
+
+    #[test]
+    fn test_move_pack_round_trip() {
+        for fen in [FEN_POS_TWO, FEN_POS_FOUR, FEN_POS_FIVE] {
+            let game = Game::read_fen(fen);
+            let (_, moves) = gen_legal_moves(&game);
+            for rev in moves {
+                assert_eq!(unpack_move(pack_move(&rev), &game), Some(rev));
+            }
         }
+    }
+
+    #[test]
+    fn test_sizing() {
+        assert_eq!(TTTable::init(1).table.len(), 1 << 14);
+        assert_eq!(TTTable::init(3).table.len(), 1 << 15);
+        assert_eq!(TTTable::init(0).table.len(), TTTable::init(1).table.len());
+        assert_eq!(TTTable::with_buckets(0).table.len(), 1);
+    }
+
+    #[test]
+    fn test_set_and_get() {
+        let tt = TTTable::init(1);
+        let game = Game::read_fen(FEN_POS_TWO);
+        let (_, moves) = gen_legal_moves(&game);
+
+        let data = TTData { rev: moves[0], score: -125, eval: 40, depth: 7, bound: Bound::Upper };
+        tt.set(game.key, 3, data);
+        let entry = tt.get(game.key).unwrap();
+        let mv = pack_move(&moves[0]);
+        assert_eq!(entry, TTEntry::init(game.key, mv, -125, 40, 7, Bound::Upper, 0));
+        assert_eq!(entry.rev(&game), Some(moves[0]));
+
+        // Same bucket, different check
+        assert_eq!(tt.get(game.key ^ 1), None);
+        tt.clear();
+        assert_eq!(tt.get(game.key), None);
+    }
+
+    #[test]
+    fn test_torn_entry_is_a_miss() {
+        let tt = TTTable::init(1);
+        let game = Game::read_fen(FEN_POS_TWO);
+        let (_, moves) = gen_legal_moves(&game);
+
+        let data = TTData { rev: moves[0], score: 10, eval: 0, depth: 5, bound: Bound::Exact };
+        tt.set(game.key, 0, data);
+        // Another thread wrote only the data half of its entry into the same slot
+        let mv = pack_move(&moves[1]);
+        let other = pack_entry(&TTEntry::init(game.key, mv, 20, 0, 9, Bound::Lower, 0));
+        tt.table[tt.idx(game.key)].data[0].store(other, Ordering::Relaxed);
 
-        line.push(mv.rev);
+        assert_eq!(tt.get(game.key), None);
+    }
+
+    #[test]
+    fn test_replacement() {
+        let tt = TTTable::init(1);
+        let game = Game::read_fen(FEN_POS_TWO);
+        let (_, moves) = gen_legal_moves(&game);
+        let data = |depth| TTData { rev: moves[0], score: 0, eval: 0, depth, bound: Bound::Exact };
+        // The keys differ only in the low bits, so they all land in the same bucket
+        let keys: Vec<u64> = (0..8).map(|i| game.key ^ i).collect();
 
-        if move_exists(game, &mv.rev) {
-            game.make_move(&mv.rev, &PositionIrr::init_with_game(game));
-            pos_key = game.key;
-        } else {
-            break;
+        for &key in &keys[..DEPTH_SLOTS] {
+            tt.set(key, 0, data(9));
         }
+
+        // Shallow results of other positions only take the always-replace slot
+        tt.set(keys[4], 0, data(3));
+        tt.set(keys[5], 0, data(2));
+        assert!(keys[..DEPTH_SLOTS].iter().all(|&key| tt.get(key).is_some()));
+        assert_eq!(tt.get(keys[4]), None);
+        assert_eq!(tt.get(keys[5]).map(|e| e.depth), Some(2));
+
+        // Once the next search starts the deep entries are stale and replaced first
+        tt.new_search();
+        assert_eq!(tt.hashfull(), 0);
+        tt.set(keys[6], 0, data(1));
+        assert_eq!(tt.get(keys[6]).map(|e| (e.depth, e.age)), Some((1, 1)));
+        assert_eq!(tt.get(keys[5]).map(|e| e.depth), Some(2));
+        assert_eq!(keys[..DEPTH_SLOTS].iter().filter(|&&key| tt.get(key).is_some()).count(), 3);
     }
 
-    while game.ply > 0 {
-        game.undo_move();
+    #[test]
+    fn test_same_key_keeps_deep_entry() {
+        let tt = TTTable::init(1);
+        let game = Game::read_fen(FEN_POS_TWO);
+        let (_, moves) = gen_legal_moves(&game);
+        let data = |rev, depth, bound| TTData { rev, score: 0, eval: 0, depth, bound };
+
+        // A quiescence result doesn't replace a deep entry, one nearly as deep does
+        tt.set(game.key, 0, data(moves[0], 10, Bound::Lower));
+        tt.set(game.key, 0, data(moves[1], 0, Bound::Lower));
+        assert_eq!(tt.get(game.key).map(|e| e.depth), Some(10));
+        tt.set(game.key, 0, data(moves[1], 7, Bound::Upper));
+        assert_eq!(tt.get(game.key).map(|e| e.depth), Some(7));
+
+        // An exact score always does, and so does anything once the entry is stale
+        tt.set(game.key, 0, data(moves[0], 0, Bound::Exact));
+        assert_eq!(tt.get(game.key).map(|e| e.depth), Some(0));
+        tt.set(game.key, 0, data(moves[0], 12, Bound::Lower));
+        tt.new_search();
+        tt.set(game.key, 0, data(moves[1], 1, Bound::Upper));
+        assert_eq!(tt.get(game.key).map(|e| (e.depth, e.age)), Some((1, 1)));
     }
 
-    line
+    #[test]
+    fn test_hashfull() {
+        let tt = TTTable::init(1);
+        assert_eq!(tt.hashfull(), 0);
+
+        let game = Game::read_fen(FEN_POS_TWO);
+        let (_, moves) = gen_legal_moves(&game);
+        let data = |depth| TTData { rev: moves[0], score: 0, eval: 0, depth, bound: Bound::Exact };
+        // One entry in each of the first 100 buckets is 100 out of the 1000 sampled entries
+        let bucket_size = u64::MAX / tt.table.len() as u64 + 1;
+        for i in 0..100 {
+            tt.set(i * bucket_size, 0, data(1));
+        }
+        assert_eq!(tt.hashfull(), 100);
+    }
 }
@@ -2,6 +2,7 @@ use std::io::stdin;
 use std::io::stdout;
 use std::io::Write;
 use std::process::exit;
+use std::sync::Arc;
 
 use rand::Rng;
 
@@ -10,7 +11,8 @@ use crate::engine::move_generation::make_move::GameMoveTrait;
 use crate::engine::move_generation::mv_gen::gen_moves;
 use crate::engine::move_generation::mv_gen::is_repetition;
 use crate::engine::search::searcher::iterative_deepening;
-use crate::engine::search::transposition_table::get_line;
+use crate::engine::search::transposition_table::TTTable;
+use crate::engine::search::transposition_table::DEFAULT_HASH_MB;
 use crate::engine::shared::helper_func::print_utility::move_notation;
 use crate::engine::shared::helper_func::print_utility::print_chess;
 use crate::engine::shared::helper_func::print_utility::print_move_list;
@@ -19,6 +21,7 @@ use crate::engine::shared::structures::internal_move::Flag;
 pub fn play_chess(game: &mut Game) {
     // let mut move_list: Vec<InternalMove>;
     let (mut irr, mut pos_rev) = gen_moves(game.color, game);
+    let tt = Arc::new(TTTable::init(DEFAULT_HASH_MB));
 
     loop {
         let mut s = String::new();
@@ -34,10 +37,12 @@ pub fn play_chess(game: &mut Game) {
                 print_chess(game);
             }
             "l" => {
-                print_move_list(&get_line(game, game.key));
+                if let Some(rm) = game.info.root_moves.first() {
+                    print_move_list(&rm.pv);
+                }
             }
             "a" => {
-                println!("{:#?}", game.tt.table);
+                println!("{:#?}", game.info.shared.tt.get(game.key));
             }
             "m" => {
                 (irr, pos_rev) = gen_moves(game.color, game);
@@ -52,7 +57,7 @@ pub fn play_chess(game: &mut Game) {
                 }
             }
             "s" => {
-                iterative_deepening(game);
+                iterative_deepening(game, &tt);
                 // println!("{:#?}", game.moves.last());
             }
             "g" => {
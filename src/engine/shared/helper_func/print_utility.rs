@@ -9,6 +9,7 @@ use crate::engine::move_generation::mv_gen::move_exists;
 use crate::engine::shared::helper_func::bit_pos_utility::*;
 use crate::engine::shared::helper_func::bitboard::Bitboard;
 use crate::engine::shared::helper_func::const_utility::*;
+use crate::engine::shared::structures::castling_struct::{KING_SIDE, QUEEN_SIDE};
 use crate::engine::shared::structures::internal_move::*;
 use crate::engine::shared::structures::piece::*;
 use crate::engine::shared::structures::square::*;
@@ -71,15 +72,11 @@ pub fn print_move_list(moves: &[PositionRev]) {
     }
 }
 
-pub fn get_move_list(moves: &[PositionRev]) -> String {
+pub fn get_move_list(moves: &[PositionRev], game: &Game) -> String {
     let mut move_list_resp: String = String::new();
     for mv in moves {
-        let promotion = match mv.flag {
-            Flag::Promotion(piece, _) => Some(piece),
-            _ => None,
-        };
         move_list_resp.push_str(" ");
-        move_list_resp.push_str(move_notation(mv.from, mv.to, promotion).as_str());
+        move_list_resp.push_str(uci_move_notation(mv, game).as_str());
     }
 
     return move_list_resp;
@@ -95,6 +92,19 @@ pub fn move_notation(sq_from: u8, sq_to: u8, promotion: Option<Piece>) -> String
     }
 }
 
+/// Notation sent to the GUI. With `UCI_Chess960` enabled castling is written as the king
+/// taking its own rook (e1h1 instead of e1g1).
+pub fn uci_move_notation(rev: &PositionRev, game: &Game) -> String {
+    let side = match rev.flag {
+        Flag::KingCastle if game.chess960 => KING_SIDE,
+        Flag::QueenCastle if game.chess960 => QUEEN_SIDE,
+        _ => return move_notation(rev.from, rev.to, rev.flag.get_promo_piece()),
+    };
+
+    let rook_sq = game.castling_sq.rook[rev.piece.color().idx()][side];
+    move_notation(rev.from, rook_sq as u8, None)
+}
+
 pub fn sq_notation(square: u8) -> String {
     let (rank, file) = idx_to_position(square as usize, None);
     format!("{}{}", FILE_LETTERS[file], rank + 1)
@@ -104,8 +114,7 @@ pub fn from_move_notation(notation: &str, game: &Game) -> (PositionIrr, Position
     let (irr, pos_rev) = gen_moves(game.color, game);
 
     for rev in &pos_rev {
-        let mv_notation =
-            move_notation(rev.from, rev.to, rev.flag.get_promo_piece()).to_lowercase();
+        let mv_notation = uci_move_notation(rev, game).to_lowercase();
         if notation == mv_notation {
             return (irr, *rev);
         }
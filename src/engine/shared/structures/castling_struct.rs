@@ -1,22 +1,41 @@
 use bitflags::bitflags;
 
 use super::color::*;
-use super::square::SqPos;
 use crate::engine::game::Game;
+use crate::engine::move_generation::legal_mv_gen::between;
 use crate::engine::move_generation::mv_gen::*;
+use crate::engine::shared::helper_func::bitboard::Iterator;
+use crate::engine::shared::structures::piece::PieceTrait;
 use crate::engine::shared::structures::square::SqPos::*;
 
-pub const CASTLE_DATA: [(usize, usize, CastlingRights, Color); 4] = [
-    (H1 as usize, E1 as usize, CastlingRights::WKINGSIDE, WHITE),
-    (A1 as usize, E1 as usize, CastlingRights::WQUEENSIDE, WHITE),
-    (H8 as usize, E8 as usize, CastlingRights::BKINGSIDE, BLACK),
-    (A8 as usize, E8 as usize, CastlingRights::BQUEENSIDE, BLACK),
-];
+pub const KING_SIDE: usize = 0;
+pub const QUEEN_SIDE: usize = 1;
 
-pub const ROOK_SQ: [[(usize, usize); 2]; 2] = [
-    [(SqPos::H1 as usize, SqPos::F1 as usize), (SqPos::A1 as usize, SqPos::D1 as usize)],
-    [(SqPos::H8 as usize, SqPos::F8 as usize), (SqPos::A8 as usize, SqPos::D8 as usize)],
-];
+/// Where the king and the rook end up after castling, the same in standard chess and Chess960.
+pub const CASTLE_KING_TO: [[usize; 2]; 2] =
+    [[G1 as usize, C1 as usize], [G8 as usize, C8 as usize]];
+pub const CASTLE_ROOK_TO: [[usize; 2]; 2] =
+    [[F1 as usize, D1 as usize], [F8 as usize, D8 as usize]];
+
+/// Start squares of the castling king and rooks, indexed by color (and side for the rooks).
+/// In Chess960 they depend on the starting position, so every game carries its own copy.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct CastlingSquares {
+    pub king: [usize; 2],
+    pub rook: [[usize; 2]; 2],
+}
+
+impl CastlingSquares {
+    pub const STANDARD: CastlingSquares = CastlingSquares {
+        king: [E1 as usize, E8 as usize],
+        rook: [[H1 as usize, A1 as usize], [H8 as usize, A8 as usize]],
+    };
+
+    #[inline(always)]
+    pub fn is_standard(&self) -> bool {
+        *self == Self::STANDARD
+    }
+}
 
 bitflags! {
     #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -61,57 +80,99 @@ impl CastlingRights {
         self.val() & castle.val() != 0
     }
 
+    /// Right of `color` to castle to the given side.
     #[inline(always)]
-    pub fn sq_empty(&self, castling: CastlingRights, own: u64, enemy: u64) -> bool {
-        let occ = own | enemy;
-        let resp = match castling {
-            CastlingRights::WKINGSIDE => occ & ((1 << F1.idx()) | (1 << G1.idx())),
-            CastlingRights::WQUEENSIDE => {
-                occ & ((1 << D1.idx()) | (1 << C1.idx()) | (1 << B1.idx()))
-            }
-            CastlingRights::BKINGSIDE => occ & ((1 << F8.idx()) | (1 << G8.idx())),
-            CastlingRights::BQUEENSIDE => {
-                occ & ((1 << D8.idx()) | (1 << C8.idx()) | (1 << B8.idx()))
-            }
-            _ => panic!("Invalid Castling Rights"),
-        };
+    pub fn from_side(color: Color, side: usize) -> CastlingRights {
+        CastlingRights::from_bits_truncate(1 << (2 * color as usize + side))
+    }
 
-        resp == 0
+    /// Every square the king and the rook pass over (destinations included) has to be empty,
+    /// apart from the two castling pieces themselves.
+    #[inline(always)]
+    pub fn sq_empty(&self, color: Color, side: usize, game: &Game) -> bool {
+        let king_from = game.castling_sq.king[color.idx()];
+        let rook_from = game.castling_sq.rook[color.idx()][side];
+        let king_to = CASTLE_KING_TO[color.idx()][side];
+        let rook_to = CASTLE_ROOK_TO[color.idx()][side];
+
+        let occ =
+            (game.bitboard(WHITE) | game.bitboard(BLACK)) & !(1 << king_from | 1 << rook_from);
+        let path = between(king_from, king_to)
+            | between(rook_from, rook_to)
+            | (1 << king_to)
+            | (1 << rook_to);
+
+        occ & path == 0
     }
 
+    /// Checks if any square on the king's path (start and destination included) is attacked.
+    /// The castling rook is taken off the board, since in Chess960 it can be the piece that
+    /// shields the destination square from an enemy rook or queen.
     #[inline(always)]
-    pub fn sq_att(&self, castle: CastlingRights, game: &Game, _own: u64, _enemy: u64) -> bool {
-        let resp = match castle {
-            CastlingRights::WKINGSIDE => {
-                sq_attack(game, E1.idx(), WHITE)
-                    | sq_attack(game, F1.idx(), WHITE)
-                    | sq_attack(game, G1.idx(), WHITE)
-            }
-            CastlingRights::WQUEENSIDE => {
-                sq_attack(game, E1.idx(), WHITE)
-                    | sq_attack(game, D1.idx(), WHITE)
-                    | sq_attack(game, C1.idx(), WHITE)
-            }
-            CastlingRights::BKINGSIDE => {
-                sq_attack(game, E8.idx(), BLACK)
-                    | sq_attack(game, F8.idx(), BLACK)
-                    | sq_attack(game, G8.idx(), BLACK)
-            }
-            CastlingRights::BQUEENSIDE => {
-                sq_attack(game, E8.idx(), BLACK)
-                    | sq_attack(game, D8.idx(), BLACK)
-                    | sq_attack(game, C8.idx(), BLACK)
+    pub fn sq_att(&self, color: Color, side: usize, game: &Game) -> bool {
+        let king_from = game.castling_sq.king[color.idx()];
+        let rook_from = game.castling_sq.rook[color.idx()][side];
+        let king_to = CASTLE_KING_TO[color.idx()][side];
+
+        let occ = (game.bitboard(WHITE) | game.bitboard(BLACK)) & !(1 << rook_from);
+        let mut path = between(king_from, king_to) | (1 << king_from) | (1 << king_to);
+
+        while let Some(sq) = path.next() {
+            if attackers_to(game, sq, occ) & game.bitboard(color.opp()) != 0 {
+                return true;
             }
-            _ => panic!("Invalid Castling Rights"),
-        };
+        }
 
-        resp != 0
+        false
     }
 
     #[inline(always)]
-    pub fn valid(&self, castle: CastlingRights, game: &Game, own: u64, enemy: u64) -> bool {
-        self.is_set(castle)
-            && self.sq_empty(castle, own, enemy)
-            && !self.sq_att(castle, game, own, enemy)
+    pub fn valid(&self, color: Color, side: usize, game: &Game) -> bool {
+        self.is_set(CastlingRights::from_side(color, side))
+            && self.sq_empty(color, side, game)
+            && !self.sq_att(color, side, game)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{
+        fen::fen::FenTrait,
+        move_generation::{legal_mv_gen::gen_legal_moves, make_move::GameMoveTrait},
+        shared::structures::{
+            internal_move::Flag,
+            piece::{WHITE_KING, WHITE_ROOK},
+        },
+    };
+
+    #[test]
+    fn test_castling_rook_shields_destination() {
+        // The b1 rook blocks the a1 queen only until it castles to d1
+        let game = Game::read_fen("4k3/8/8/8/8/8/8/qR1K4 w B - 0 1");
+        assert!(game.castling.sq_empty(WHITE, QUEEN_SIDE, &game));
+        assert!(game.castling.sq_att(WHITE, QUEEN_SIDE, &game));
+        assert!(!game.castling.valid(WHITE, QUEEN_SIDE, &game));
+    }
+
+    #[test]
+    fn test_castle_king_stays_in_place() {
+        // The f1 rook is in the way of the castling rook
+        let blocked = Game::read_fen("4k3/8/8/8/8/8/8/5RKR w H - 0 1");
+        assert!(!blocked.castling.valid(WHITE, KING_SIDE, &blocked));
+
+        let mut game = Game::read_fen("4k3/8/8/8/8/8/8/6KR w H - 0 1");
+        let before = game.clone();
+        let (irr, moves) = gen_legal_moves(&game);
+        let castle = moves.iter().find(|rev| rev.flag == Flag::KingCastle).unwrap();
+        assert_eq!((castle.from, castle.to), (G1 as u8, G1 as u8));
+
+        game.make_legal_move(castle, &irr);
+        assert_eq!(game.squares[F1 as usize], Some(WHITE_ROOK));
+        assert_eq!(game.squares[G1 as usize], Some(WHITE_KING));
+        assert_eq!(game.castling, CastlingRights::NONE);
+
+        game.undo_move();
+        assert_eq!(game, before);
     }
 }
@@ -22,6 +22,11 @@ impl Flag {
         }
     }
 
+    /// Moves that neither capture nor promote.
+    pub fn is_quiet(&self) -> bool {
+        matches!(*self, Flag::Quiet | Flag::KingCastle | Flag::QueenCastle)
+    }
+
     pub fn get_promo_piece(&self) -> Option<Piece> {
         match *self {
             Flag::Promotion(piece, _) => Some(piece),
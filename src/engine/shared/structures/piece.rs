@@ -46,7 +46,8 @@ const BISHOP_WT: isize = 350;
 const KNIGHT_WT: isize = 325;
 const PAWN_WT: isize = 100;
 
-pub const PIECE_WT: [isize; 6] = [PAWN_WT, KNIGHT_WT, BISHOP_WT, ROOK_WT, QUEEN_WT, KING_WT];
+// Indexed by the piece code (kind / 2 - 1), not by the order of `PIECES`
+pub const PIECE_WT: [isize; 6] = [PAWN_WT, KNIGHT_WT, KING_WT, BISHOP_WT, ROOK_WT, QUEEN_WT];
 
 pub trait PieceTrait {
     fn color(&self) -> Color;
@@ -215,6 +216,16 @@ mod tests {
         assert_eq!(BLACK_KNIGHT.color(), BLACK);
     }
 
+    #[test]
+    fn test_piece_weight() {
+        assert_eq!(WHITE_PAWN.weight(), PAWN_WT);
+        assert_eq!(BLACK_KNIGHT.weight(), KNIGHT_WT);
+        assert_eq!(WHITE_BISHOP.weight(), BISHOP_WT);
+        assert_eq!(BLACK_ROOK.weight(), ROOK_WT);
+        assert_eq!(WHITE_QUEEN.weight(), QUEEN_WT);
+        assert_eq!(BLACK_KING.weight(), KING_WT);
+    }
+
     #[test]
     fn test_piece_kind() {
         assert_eq!(WHITE_ROOK.kind(), ROOK);
@@ -16,18 +16,26 @@ pub mod engine {
         pub mod fen;
     }
     pub mod move_generation {
+        pub mod legal_mv_gen;
         pub mod make_move;
         pub mod mv_gen;
         pub mod perft;
     }
     pub mod evaluation {
         pub mod evaluation;
+        pub mod see;
     }
     pub mod protocols {
+        pub mod options;
         pub mod time;
         pub mod uci;
     }
     pub mod search {
+        pub mod bench;
+        pub mod history;
+        pub mod move_picker;
+        pub mod pv_table;
+        pub mod score;
         pub mod searcher;
         pub mod time;
         pub mod transposition_table;
@@ -3,6 +3,7 @@ use std::env;
 use ::engine::engine::protocols::uci::UCI;
 use engine::fen::fen::FenTrait;
 use engine::game::Game;
+use engine::search::bench::{bench, BenchConfig};
 use engine::search::searcher::SearchInfo;
 use engine::shared::helper_func::const_utility::{FEN_MATE_IN_3, FEN_MATE_IN_4, FEN_MATE_IN_5};
 use engine::shared::helper_func::play_chess_utility::play_chess;
@@ -12,6 +13,15 @@ pub mod engine;
 fn main() {
     // FIXME: Needed to backtrace the call stack
     env::set_var("RUST_BACKTRACE", "1");
+
+    // chess-engine bench [depth] [hash] [threads]
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().is_some_and(|cmd| cmd == "bench") {
+        let args: Vec<&str> = args[1..].iter().map(String::as_str).collect();
+        bench(BenchConfig::parse(&args));
+        return;
+    }
+
     let mut uci = UCI::init();
     uci.main();
 